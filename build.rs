@@ -0,0 +1,4 @@
+// Embedded migrations are only picked up when the build script reruns.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,7 @@
+-- Edges keep the direction they were inserted in, so oneway streets stay oneway.
+-- Existing edges were normalised to be undirected, which is what two-way means.
+DROP TRIGGER IF EXISTS trigger_osm_node_edge_undirected ON osm_node_edge;
+DROP FUNCTION IF EXISTS enforce_node_order();
+
+-- Only rideable from source to target when true, otherwise rideable both ways.
+ALTER TABLE osm_node_edge ADD COLUMN oneway BOOLEAN NOT NULL DEFAULT false;
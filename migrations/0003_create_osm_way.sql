@@ -0,0 +1,19 @@
+-- Road attributes of every cyclable way, for routing and output.
+CREATE TABLE osm_way (
+    -- way_id for ways in `*.osm[.pbf]` maps.
+    id BIGINT PRIMARY KEY,
+    highway TEXT,
+    name TEXT,
+    surface TEXT,
+    smoothness TEXT,
+    tracktype TEXT,
+    maxspeed TEXT,
+    lit TEXT,
+    access TEXT,
+    bicycle TEXT
+);
+
+-- Edges stored before ways were kept have no way until the ways are extracted again.
+ALTER TABLE osm_node_edge ADD COLUMN way_id BIGINT REFERENCES osm_way(id);
+
+CREATE INDEX index_osm_node_edge_way_id ON osm_node_edge (way_id);
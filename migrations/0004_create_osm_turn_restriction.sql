@@ -0,0 +1,14 @@
+-- Turns between ways that bicycles may not take, or the only turns they may take.
+CREATE TABLE osm_turn_restriction (
+    -- relation_id for `type=restriction` relations in `*.osm[.pbf]` maps.
+    relation_id BIGINT NOT NULL,
+    -- Restricted ways may not be cyclable, so these don't reference osm_way.
+    from_way_id BIGINT NOT NULL,
+    via_node_id BIGINT NOT NULL,
+    to_way_id BIGINT NOT NULL,
+    -- true for `only_*` restrictions, false for `no_*` restrictions.
+    only BOOLEAN NOT NULL,
+    PRIMARY KEY (relation_id, from_way_id, to_way_id)
+);
+
+CREATE INDEX index_osm_turn_restriction_via_node_id ON osm_turn_restriction (via_node_id);
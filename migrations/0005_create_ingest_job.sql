@@ -0,0 +1,17 @@
+-- Every run of a bootstrap stage against a source file, so bootstrapping can be
+-- resumed and re-run without applying the same file twice.
+CREATE TABLE ingest_job (
+    id BIGSERIAL PRIMARY KEY,
+    -- ingest, ways, coordinates or elevations.
+    stage TEXT NOT NULL,
+    source_path TEXT NOT NULL,
+    -- sha256 of the source file, so a renamed file is still recognised.
+    source_hash TEXT NOT NULL,
+    -- running, completed or failed.
+    status TEXT NOT NULL,
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX index_ingest_job_stage_source_hash ON ingest_job (stage, source_hash);
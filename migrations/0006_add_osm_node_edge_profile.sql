@@ -0,0 +1,8 @@
+-- Elevation changes from source to target, sampled from a DEM between the nodes
+-- so edges crossing a valley aren't mistaken for flat ground.
+-- Either all are set or none are, until the profiles are extracted.
+ALTER TABLE osm_node_edge
+    ADD COLUMN climbing DOUBLE PRECISION,
+    ADD COLUMN descending DOUBLE PRECISION,
+    ADD COLUMN max_gradient DOUBLE PRECISION,
+    ADD COLUMN min_gradient DOUBLE PRECISION;
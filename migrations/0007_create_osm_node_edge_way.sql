@@ -0,0 +1,19 @@
+-- Every way an edge belongs to, since ways can share a pair of nodes.
+-- `osm_node_edge.way_id` is one of these, so deleting a way only removes
+-- the edges no other way still covers.
+CREATE TABLE osm_node_edge_way (
+    source_node_id BIGINT NOT NULL,
+    target_node_id BIGINT NOT NULL,
+    way_id BIGINT NOT NULL REFERENCES osm_way(id),
+    -- Only rideable from the source node to the target node along this way.
+    oneway BOOLEAN NOT NULL,
+    PRIMARY KEY (source_node_id, target_node_id, way_id),
+    FOREIGN KEY (source_node_id, target_node_id)
+        REFERENCES osm_node_edge(source_node_id, target_node_id) ON DELETE CASCADE
+);
+
+CREATE INDEX index_osm_node_edge_way_way_id ON osm_node_edge_way (way_id);
+
+INSERT INTO osm_node_edge_way(source_node_id,target_node_id,way_id,oneway)
+SELECT source_node_id, target_node_id, way_id, oneway FROM osm_node_edge
+WHERE way_id IS NOT NULL;
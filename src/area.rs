@@ -0,0 +1,83 @@
+use geo::{Coord, Destination, Haversine, Point, Rect};
+
+/// A circle on the surface of the earth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchArea {
+    /// Longitude and latitude in degrees.
+    pub centre: Coord,
+    /// Metres from the centre.
+    pub radius: f64,
+}
+
+impl SearchArea {
+    pub fn from_kilometres(x: f64, y: f64, kilometres: f64) -> Self {
+        Self {
+            centre: Coord { x, y },
+            radius: kilometres * 1_000.0,
+        }
+    }
+
+    /// The smallest longitude and latitude envelope containing the circle,
+    /// cheap to compare against before measuring exact distances.
+    pub fn bounding_rect(&self) -> Rect {
+        let centre = Point::from(self.centre);
+        let north = Haversine::destination(centre, 0.0, self.radius);
+        let east = Haversine::destination(centre, 90.0, self.radius);
+        let south = Haversine::destination(centre, 180.0, self.radius);
+        let west = Haversine::destination(centre, 270.0, self.radius);
+
+        Rect::new(
+            Coord {
+                x: west.x(),
+                y: south.y(),
+            },
+            Coord {
+                x: east.x(),
+                y: north.y(),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::area::SearchArea;
+    use geo::{Contains, Coord, Destination, Haversine, Point};
+
+    #[test]
+    fn radius_is_metres() {
+        let area = SearchArea::from_kilometres(144.96, -37.81, 10.0);
+
+        assert_eq!(area.radius, 10_000.0);
+        assert_eq!(
+            area.centre,
+            Coord {
+                x: 144.96,
+                y: -37.81
+            }
+        );
+    }
+
+    #[test]
+    fn bounding_rect_fits_the_circle() {
+        let area = SearchArea::from_kilometres(144.96, -37.81, 10.0);
+        let rect = area.bounding_rect();
+        let centre = Point::from(area.centre);
+
+        // Roughly 0.09 degrees of latitude and 0.114 degrees of longitude at -37.81.
+        assert!((rect.height() - 0.18).abs() < 0.001);
+        assert!((rect.width() - 0.228).abs() < 0.001);
+
+        for bearing in [0.0, 45.0, 90.0, 135.0, 180.0, 225.0, 270.0, 315.0] {
+            let inside = Haversine::destination(centre, bearing, 9_900.0);
+            let outside = Haversine::destination(centre, bearing, 10_100.0);
+
+            assert!(rect.contains(&inside));
+
+            // Diagonals leave the circle before they leave the envelope.
+            if bearing % 90.0 == 0.0 {
+                assert!(!rect.contains(&outside));
+            }
+        }
+    }
+}
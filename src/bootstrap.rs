@@ -0,0 +1,656 @@
+use crate::{
+    elevation::Missing,
+    jobs::{is_applied, JobStatus, Source, Stage},
+    osc::OsmChange,
+    osm::CyclableWays,
+    rules::WayRules,
+    segment::EdgeProfile,
+    store::GraphStore,
+};
+use anyhow::{anyhow, Result};
+use geo::{Coord, Distance, Haversine, Rect};
+use itertools::Itertools;
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+};
+
+/// Runs a stage for a source unless it was already applied, recording the outcome
+/// so bootstrapping can resume from the first source that didn't complete.
+///
+/// Returns false when the stage was skipped.
+pub async fn run_job(
+    store: &impl GraphStore,
+    stage: Stage,
+    source: &Source,
+    force: bool,
+    job: impl Future<Output = Result<()>>,
+) -> Result<bool> {
+    if !force && is_applied(stage, source, &store.query_jobs().await?) {
+        info!(
+            "Already applied {} from {}, skipping",
+            stage.as_str(),
+            source.path
+        );
+        return Ok(false);
+    }
+
+    let job_id = store.insert_job(stage, source).await?;
+
+    match job.await {
+        Ok(()) => {
+            store
+                .update_job_status(job_id, JobStatus::Completed, None)
+                .await?;
+            Ok(true)
+        }
+        Err(err) => {
+            store
+                .update_job_status(job_id, JobStatus::Failed, Some(format!("{:#}", err)))
+                .await?;
+            Err(err)
+        }
+    }
+}
+
+/// Stores every node, way, edge and turn restriction of the cyclable graph.
+pub async fn insert_ways(store: &impl GraphStore, cyclable: CyclableWays) -> Result<()> {
+    let nodes = cyclable.graph.nodes().collect_vec();
+    let edges = cyclable
+        .graph
+        .all_edges()
+        .map(|(a, b, edge)| (a, b, *edge))
+        .chain(cyclable.shared_edges)
+        .collect_vec();
+
+    store.insert_node_ids(nodes).await?;
+    store.insert_way_tags(cyclable.ways).await?;
+    store.insert_edges(edges).await?;
+    store
+        .insert_turn_restrictions(cyclable.restrictions)
+        .await?;
+
+    Ok(())
+}
+
+/// Stores the cyclable graph with the coordinates of its nodes, read in the same pass.
+///
+/// Nodes cut off by the edge of the map are stored without a coordinate.
+pub async fn ingest(
+    store: &impl GraphStore,
+    cyclable: CyclableWays,
+    coords: HashMap<i64, Coord>,
+) -> Result<()> {
+    store.insert_nodes(coords).await?;
+    insert_ways(store, cyclable).await
+}
+
+/// Applies an OSM change file to the stored graph.
+///
+/// Changed ways are replaced, which removes ways that are no longer cyclable
+/// along with the nodes no way uses anymore, and nodes only lose their elevation
+/// when they moved.
+///
+/// Ways that became cyclable without their nodes changing bring nodes the change file
+/// has no coordinates for, which are counted and left for `insert_coordinates`.
+pub async fn apply_changes(
+    store: &impl GraphStore,
+    change: OsmChange,
+    rules: &WayRules,
+) -> Result<()> {
+    let cyclable = change.cyclable_ways(rules);
+
+    store.delete_ways(change.changed_way_ids()).await?;
+    insert_ways(store, cyclable).await?;
+    store.update_coordinates(change.nodes).await?;
+    store.delete_nodes(change.deleted_node_ids).await?;
+    store.delete_orphaned_nodes().await?;
+
+    let without_coords = store.query_node_ids().await?.len();
+
+    if without_coords > 0 {
+        warn!(
+            "Left {} nodes without coordinates, run `bootstrap coordinates` to fill them in",
+            without_coords
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads coordinates for the stored nodes that don't have one yet.
+pub async fn insert_coordinates(
+    store: &impl GraphStore,
+    read_coords: impl FnOnce(&HashSet<i64>) -> Result<HashMap<i64, Coord>>,
+) -> Result<()> {
+    let cycleable_node_ids = store.query_node_ids().await?;
+
+    info!("Reading nodes");
+    // only keep node_ids we can cycle, which we updated in our database earlier.
+    let coords = read_coords(&cycleable_node_ids)?;
+    info!("Read nodes");
+
+    store.update_coordinates(coords).await?;
+
+    Ok(())
+}
+
+/// Finds elevations for the stored nodes within `rect` that don't have one yet.
+///
+/// Nodes without an elevation are left for another raster to fill in and counted,
+/// rather than failing every node in the raster.
+pub async fn insert_elevations(
+    store: &impl GraphStore,
+    rect: Rect,
+    find_elevation: impl Fn(&Coord) -> Result<f64, Missing>,
+) -> Result<()> {
+    let rows = store.query_containing_coords(rect).await?;
+
+    if rows.is_empty() {
+        info!("No coordinates, skipping");
+        return Ok(());
+    }
+
+    let mut elevations = Vec::with_capacity(rows.len());
+    let (mut no_data, mut outside) = (0, 0);
+
+    for (node_id, coord) in rows {
+        match find_elevation(&coord) {
+            Ok(elevation) => elevations.push((node_id, elevation)),
+            Err(Missing::NoData) => no_data += 1,
+            Err(Missing::Outside) => outside += 1,
+        }
+    }
+
+    if no_data + outside > 0 {
+        warn!(
+            "Left {} nodes without elevation, {} on NoData and {} outside the raster",
+            no_data + outside,
+            no_data,
+            outside
+        );
+    }
+
+    store.update_elevations(elevations).await?;
+
+    Ok(())
+}
+
+/// Metres between the closest samples along an edge, already finer than any DEM,
+/// so a tiny spacing can't sample each edge billions of times.
+const MIN_SPACING: f64 = 1.0;
+
+/// Samples elevations at most `spacing` metres apart along the stored edges within `rect`
+/// that don't have a profile yet, so climbs between sparse nodes aren't missed.
+///
+/// Samples follow a straight line between the coordinates of both nodes, which is
+/// indistinguishable from the great circle at the length of an edge.
+/// Edges missing any sample are left for another raster, like nodes without an elevation.
+/// Edges running off the raster are counted apart, since only a raster covering both
+/// of their nodes can profile them.
+pub async fn insert_edge_profiles(
+    store: &impl GraphStore,
+    rect: Rect,
+    spacing: f64,
+    find_elevation: impl Fn(&Coord) -> Result<f64, Missing>,
+) -> Result<()> {
+    if !(spacing.is_finite() && spacing >= MIN_SPACING) {
+        return Err(anyhow!(
+            "Expected a spacing of at least {} metres, found {}",
+            MIN_SPACING,
+            spacing
+        ));
+    }
+
+    let edges = store.query_unprofiled_edges(rect).await?;
+
+    if edges.is_empty() {
+        info!("No edges, skipping");
+        return Ok(());
+    }
+
+    let mut profiles = Vec::with_capacity(edges.len());
+    let (mut no_data, mut crossing) = (0, 0);
+
+    for (source_node_id, target_node_id, source, target) in edges {
+        let distance = Haversine::distance(source.into(), target.into());
+        let intervals = (distance / spacing).ceil().max(1.0) as usize;
+
+        let samples = (0..=intervals)
+            .map(|step| {
+                let coord = source + (target - source) * (step as f64 / intervals as f64);
+                find_elevation(&coord)
+            })
+            .collect_vec();
+
+        if samples.contains(&Err(Missing::Outside)) {
+            crossing += 1;
+        } else if let Ok(elevations) = samples.into_iter().collect::<Result<Vec<_>, _>>() {
+            profiles.push((
+                source_node_id,
+                target_node_id,
+                EdgeProfile::from_elevations(distance, &elevations),
+            ));
+        } else {
+            no_data += 1;
+        }
+    }
+
+    if no_data + crossing > 0 {
+        warn!(
+            "Left {} edges without a profile, {} on NoData and {} crossing the edge of the raster",
+            no_data + crossing,
+            no_data,
+            crossing
+        );
+    }
+
+    if crossing > 0 {
+        warn!("Merge rasters that share an edge into one file to profile the edges crossing it");
+    }
+
+    store.update_edge_profiles(profiles).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        area::SearchArea,
+        bootstrap::{apply_changes, ingest, insert_edge_profiles, insert_elevations, run_job},
+        elevation::Missing,
+        jobs::{JobStatus, Source, Stage},
+        osc::{ChangedWay, OsmChange},
+        osm::{CyclableWays, WayEdge, WayTags},
+        rules::WayRules,
+        segment::EdgeProfile,
+        store::{memory::MemoryStore, GraphStore},
+    };
+    use anyhow::anyhow;
+    use geo::{Coord, Rect};
+    use itertools::Itertools;
+    use petgraph::prelude::DiGraphMap;
+    use std::collections::{HashMap, HashSet};
+
+    /// Nodes 1, 2 and 3 along the equator, `spacing` degrees apart, joined by ways 10 then 11.
+    fn fixture(spacing: f64) -> (CyclableWays, HashMap<i64, Coord>) {
+        let edge = |way_id| WayEdge {
+            oneway: false,
+            way_id: Some(way_id),
+        };
+        let cyclable = CyclableWays {
+            graph: DiGraphMap::from_edges([(1, 2, edge(10)), (2, 3, edge(11))]),
+            ways: HashMap::from([(10, WayTags::default()), (11, WayTags::default())]),
+            restrictions: Vec::new(),
+            ..CyclableWays::default()
+        };
+        let coords = (1..=3)
+            .map(|node_id| {
+                let x = spacing * (node_id - 1) as f64;
+                (node_id, Coord { x, y: 0.0 })
+            })
+            .collect();
+
+        (cyclable, coords)
+    }
+
+    async fn bootstrap(cyclable: CyclableWays, coords: HashMap<i64, Coord>) -> MemoryStore {
+        let store = MemoryStore::default();
+        ingest(&store, cyclable, coords).await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn ingest_stores_nodes_with_coordinates() {
+        let (cyclable, mut coords) = fixture(0.001);
+        // node 3 is beyond the edge of the map
+        coords.remove(&3);
+        let store = bootstrap(cyclable, coords).await;
+
+        assert_eq!(store.query_node_ids().await.unwrap(), HashSet::from([3]));
+        assert_eq!(
+            store.query_edges_between(&[1, 2, 3]).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn ingest_again_resets_moved_nodes() {
+        let (cyclable, coords) = fixture(0.001);
+        let store = bootstrap(cyclable, coords).await;
+
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+        insert_elevations(&store, rect, |_| Ok(50.0)).await.unwrap();
+        insert_edge_profiles(&store, rect, 40.0, |_| Ok(50.0))
+            .await
+            .unwrap();
+
+        // a later extract of the map where node 1 moved north
+        let (cyclable, mut coords) = fixture(0.001);
+        coords.insert(1, Coord { x: 0.0, y: 0.0005 });
+        ingest(&store, cyclable, coords).await.unwrap();
+
+        let without_elevation = store.query_containing_coords(rect).await.unwrap();
+        assert_eq!(
+            without_elevation.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from([1])
+        );
+        assert_eq!(
+            store
+                .query_edge_profiles(&[1, 2, 3])
+                .await
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![(2, 3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn jobs_resume_after_a_failure() {
+        let store = MemoryStore::default();
+        let source = |hash: &str| Source {
+            path: format!("{}.tif", hash),
+            hash: hash.to_string(),
+        };
+
+        assert!(
+            run_job(&store, Stage::Elevations, &source("a"), false, async {
+                Ok(())
+            })
+            .await
+            .unwrap()
+        );
+        assert!(
+            run_job(&store, Stage::Elevations, &source("b"), false, async {
+                Err(anyhow!("Expected to find value"))
+            })
+            .await
+            .is_err()
+        );
+
+        // running again skips what was applied and retries what failed.
+        assert!(
+            !run_job(&store, Stage::Elevations, &source("a"), false, async {
+                Ok(())
+            })
+            .await
+            .unwrap()
+        );
+        assert!(
+            run_job(&store, Stage::Elevations, &source("b"), false, async {
+                Ok(())
+            })
+            .await
+            .unwrap()
+        );
+
+        // forcing runs it anyway.
+        assert!(
+            run_job(&store, Stage::Elevations, &source("a"), true, async {
+                Ok(())
+            })
+            .await
+            .unwrap()
+        );
+
+        let statuses = store
+            .query_jobs()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|job| job.status)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            statuses,
+            vec![
+                JobStatus::Completed,
+                JobStatus::Failed,
+                JobStatus::Completed,
+                JobStatus::Completed
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_only_reset_moved_elevations() {
+        let (cyclable, coords) = fixture(0.001);
+        let store = bootstrap(cyclable, coords).await;
+
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+        insert_elevations(&store, rect, |_| Ok(50.0)).await.unwrap();
+
+        // node 2 is touched but stays put, node 1 moves and way 11 is deleted along with node 3.
+        let change = OsmChange {
+            nodes: HashMap::from([
+                (1, Coord { x: 0.0, y: 0.0005 }),
+                (2, Coord { x: 0.001, y: 0.0 }),
+            ]),
+            deleted_node_ids: vec![3],
+            ways: vec![ChangedWay {
+                id: 10,
+                refs: vec![1, 2],
+                tags: vec![("highway".to_string(), "residential".to_string())],
+            }],
+            deleted_way_ids: vec![11],
+        };
+
+        apply_changes(&store, change, &WayRules::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.query_containing_coords(rect).await.unwrap().len(), 1);
+        assert!(store
+            .query_containing_coords(rect)
+            .await
+            .unwrap()
+            .contains_key(&1));
+
+        let area = SearchArea::from_kilometres(0.0, 0.0, 1.0);
+        let nodes = store.query_nodes_within(&area).await.unwrap();
+        assert_eq!(nodes.keys().copied().collect::<Vec<_>>(), vec![2]);
+
+        let edges = store.query_edges_between(&[1, 2, 3]).await.unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(
+            store.query_way_tags(&[10]).await.unwrap()[&10].highway,
+            Some("residential".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_keep_edges_shared_with_other_ways() {
+        // oneway way 12 runs from node 2 through 3 to 4, sharing its first edge with way 11.
+        let (mut cyclable, mut coords) = fixture(0.001);
+        let tags = [("highway", "residential"), ("oneway", "yes")];
+        cyclable.extend(CyclableWays::from_way(
+            12,
+            [2, 3, 4],
+            &tags,
+            &WayRules::default(),
+        ));
+        coords.insert(4, Coord { x: 0.003, y: 0.0 });
+        let store = bootstrap(cyclable, coords).await;
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+
+        let delete_way = |way_id| OsmChange {
+            deleted_way_ids: vec![way_id],
+            ..OsmChange::default()
+        };
+        let edge = |oneway, way_id| WayEdge {
+            oneway,
+            way_id: Some(way_id),
+        };
+        let edges = || async {
+            let edges = store.query_edges_between(&[1, 2, 3, 4]).await.unwrap();
+            edges
+                .into_iter()
+                .sorted_by_key(|(source, target, _)| (*source, *target))
+                .collect::<Vec<_>>()
+        };
+
+        apply_changes(&store, delete_way(11), &WayRules::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            edges().await,
+            vec![
+                (1, 2, edge(false, 10)),
+                (2, 3, edge(true, 12)),
+                (3, 4, edge(true, 12)),
+            ]
+        );
+
+        apply_changes(&store, delete_way(12), &WayRules::default())
+            .await
+            .unwrap();
+
+        assert_eq!(edges().await, vec![(1, 2, edge(false, 10))]);
+
+        // nodes 3 and 4 aren't on any way anymore
+        let node_ids = store.query_containing_coords(rect).await.unwrap();
+        assert_eq!(
+            node_ids.keys().copied().sorted().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_leave_unknown_nodes_without_coordinates() {
+        let (cyclable, coords) = fixture(0.001);
+        let store = bootstrap(cyclable, coords).await;
+
+        // way 12 became cyclable, but node 4 didn't change so the file has no coordinate for it.
+        let change = OsmChange {
+            nodes: HashMap::new(),
+            deleted_node_ids: Vec::new(),
+            ways: vec![ChangedWay {
+                id: 12,
+                refs: vec![3, 4],
+                tags: vec![("highway".to_string(), "residential".to_string())],
+            }],
+            deleted_way_ids: Vec::new(),
+        };
+
+        apply_changes(&store, change, &WayRules::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.query_node_ids().await.unwrap(), HashSet::from([4]));
+    }
+
+    #[tokio::test]
+    async fn missing_elevations_are_skipped() {
+        let (cyclable, coords) = fixture(0.001);
+        let store = bootstrap(cyclable, coords).await;
+
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+        insert_elevations(&store, rect, |coord| match coord.x {
+            0.0 => Ok(50.0),
+            0.001 => Err(Missing::NoData),
+            _ => Err(Missing::Outside),
+        })
+        .await
+        .unwrap();
+
+        let without_elevation = store.query_containing_coords(rect).await.unwrap();
+        assert_eq!(
+            without_elevation.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from([2, 3])
+        );
+    }
+
+    #[tokio::test]
+    async fn profiles_sample_between_nodes() {
+        // about 445 m between each node
+        let (cyclable, coords) = fixture(0.004);
+        let store = bootstrap(cyclable, coords).await;
+
+        // a creek 20 m deep halfway between the first two nodes, and no data past the second
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+        insert_edge_profiles(&store, rect, 40.0, |coord| match coord.x {
+            x if x > 0.0041 => Err(Missing::NoData),
+            x => Ok(100.0 - 20.0 * (1.0 - (x - 0.002).abs() / 0.002)),
+        })
+        .await
+        .unwrap();
+
+        let profiles = store.query_edge_profiles(&[1, 2, 3]).await.unwrap();
+        assert_eq!(profiles.len(), 1);
+
+        let EdgeProfile {
+            climbing,
+            descending,
+            max_gradient,
+            min_gradient,
+        } = profiles[&(1, 2)];
+        assert!((climbing - 20.0).abs() < 1e-6);
+        assert!((descending - 20.0).abs() < 1e-6);
+        assert!((max_gradient - 20.0 / 222.6).abs() < 1e-3);
+        assert!((min_gradient + 20.0 / 222.6).abs() < 1e-3);
+
+        assert_eq!(
+            store.query_unprofiled_edges(rect).await.unwrap(),
+            vec![(2, 3, Coord { x: 0.004, y: 0.0 }, Coord { x: 0.008, y: 0.0 })]
+        );
+
+        // moving a node means its edges have to be sampled again
+        store
+            .update_coordinates(HashMap::from([(1, Coord { x: 0.0, y: 0.001 })]))
+            .await
+            .unwrap();
+        assert!(store
+            .query_edge_profiles(&[1, 2, 3])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn edges_crossing_the_raster_are_left() {
+        let (cyclable, coords) = fixture(0.004);
+        let store = bootstrap(cyclable, coords).await;
+
+        // the raster ends between nodes 2 and 3
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 0.006, y: 1.0 });
+        assert_eq!(store.query_unprofiled_edges(rect).await.unwrap().len(), 2);
+
+        insert_edge_profiles(&store, rect, 40.0, |coord| match coord.x {
+            x if x > 0.006 => Err(Missing::Outside),
+            _ => Ok(50.0),
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store
+                .query_edge_profiles(&[1, 2, 3])
+                .await
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![(1, 2)]
+        );
+        assert_eq!(
+            store.query_unprofiled_edges(rect).await.unwrap(),
+            vec![(2, 3, Coord { x: 0.004, y: 0.0 }, Coord { x: 0.008, y: 0.0 })]
+        );
+    }
+
+    #[tokio::test]
+    async fn profiles_need_a_sensible_spacing() {
+        let (cyclable, coords) = fixture(0.001);
+        let store = bootstrap(cyclable, coords).await;
+
+        let rect = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 1.0, y: 1.0 });
+        for spacing in [0.0, 1e-9, -10.0, f64::NAN, f64::INFINITY] {
+            assert!(insert_edge_profiles(&store, rect, spacing, |_| Ok(50.0))
+                .await
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use crate::{
+    area::SearchArea,
+    cost::{BikeProfile, CostModel},
+    osm::WayTags,
+    routing::shortest_path,
+    segment::Segment,
+    store::GraphStore,
+    turns::{Turn, TurnRestrictions},
+};
+use anyhow::{anyhow, Result};
+use geo::{Coord, Distance, Haversine, Point};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use log::info;
+use petgraph::{
+    prelude::DiGraphMap,
+    visit::{Dfs, IntoNeighbors, Reversed, Visitable},
+};
+use std::collections::{HashMap, HashSet};
+
+/// A ride from the origin up to the highest point in the area and back down again.
+#[derive(Debug)]
+pub struct Circuit {
+    /// Coordinates and elevations of every node in the area, highest first.
+    pub nodes: IndexMap<i64, (Coord, f64)>,
+    /// Every direction each edge in the area may be ridden.
+    pub gradients: DiGraphMap<i64, CircuitEdge>,
+    /// Tags of the ways the edges in the area came from.
+    pub ways: HashMap<i64, WayTags>,
+    /// Node ids from the origin to the highest point.
+    pub ascent: Vec<i64>,
+    /// Node ids from the highest point back to the origin.
+    pub descent: Vec<i64>,
+}
+
+/// A direction an edge may be ridden and the way it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitEdge {
+    pub segment: Segment,
+    pub way_id: Option<i64>,
+}
+
+impl Circuit {
+    /// Node ids for the whole ride, visiting the highest point once.
+    pub fn node_ids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.ascent
+            .iter()
+            .chain(self.descent.iter().skip(1))
+            .copied()
+    }
+}
+
+// Simple solution
+//
+// Query all the nodes into HashMap<NodeId, (Coord, Elevation)>
+// Query the edges into DiGraphMap<NodeId, WayEdge>
+// Derive into IndexMap<NodeId, Gradient>
+// Flat map into GraphMap<NodeId, NodeId>, which is the node to take to travel to the intersection
+// Ride from home to bottom of biggest gradient finding path with lowest average gradient
+// Ride from top of biggest gradient to home finding path with lowest average gradient
+pub async fn find_circuit(
+    store: &impl GraphStore,
+    area: &SearchArea,
+    profile: BikeProfile,
+) -> Result<Circuit> {
+    // find the [radius] highest elevations from the given range in [radius] chunks
+    //
+    // find highest and lowest points. find the shortest path containing the biggest distances
+
+    info!("finding nodes");
+
+    // get related edges as undirected graph, then create directed graph for gradients.
+    // create graphmap of intersections with gradient diffs
+    // find the biggest diff and join it with the lowest diff.
+    let nodes = store.query_nodes_within(area).await?;
+
+    info!("finding edges");
+    let edges = store
+        .query_edges_between(&nodes.keys().copied().collect_vec())
+        .await?;
+
+    info!("finding profiles");
+    let profiles = store
+        .query_edge_profiles(&nodes.keys().copied().collect_vec())
+        .await?;
+
+    info!("finding ways");
+    let way_ids = edges
+        .iter()
+        .filter_map(|(_, _, edge)| edge.way_id)
+        .unique()
+        .collect_vec();
+    let ways = store.query_way_tags(&way_ids).await?;
+
+    // edges without a stored way are assumed to be rideable
+    let surface_penalty = |way_id: Option<i64>| {
+        way_id
+            .and_then(|way_id| ways.get(&way_id))
+            .map_or(Some(1.0), |tags| profile.surface_penalty(tags))
+    };
+
+    info!("finding turn restrictions");
+    let restrictions: TurnRestrictions = store
+        .query_turn_restrictions(&nodes.keys().copied().collect_vec())
+        .await?
+        .into_iter()
+        .collect();
+
+    // edges without a stored way can always be turned onto
+    let can_turn = |(previous_node_id, via_node_id, from): (i64, i64, &CircuitEdge),
+                    (_, next_node_id, to): (i64, i64, &CircuitEdge)| {
+        match (from.way_id, to.way_id) {
+            (Some(from_way_id), Some(to_way_id)) => restrictions.permits(Turn {
+                from_way_id,
+                previous_node_id,
+                via_node_id,
+                next_node_id,
+                to_way_id,
+            }),
+            _ => true,
+        }
+    };
+
+    info!("finding gradients");
+    let gradients: DiGraphMap<i64, CircuitEdge> = edges
+        .into_iter()
+        // the bike can't ride these ways at all
+        .filter(|(_, _, edge)| surface_penalty(edge.way_id).is_some())
+        .map(|(source_node_id, target_node_id, edge)| -> Result<_> {
+            let source = nodes
+                .get(&source_node_id)
+                .ok_or_else(|| anyhow!("Expected to find source from node_id"))?;
+
+            let target = nodes
+                .get(&target_node_id)
+                .ok_or_else(|| anyhow!("Expected to find target from node_id"))?;
+
+            let profile = profiles.get(&(source_node_id, target_node_id));
+            let segment = Segment::along(*source, *target, profile);
+
+            let way_id = edge.way_id;
+            let source_edge = (
+                source_node_id,
+                target_node_id,
+                CircuitEdge { segment, way_id },
+            );
+            let target_edge = (
+                target_node_id,
+                source_node_id,
+                CircuitEdge {
+                    segment: segment.reversed(),
+                    way_id,
+                },
+            );
+
+            // oneway edges can't be ridden back from the target
+            Ok(if edge.oneway {
+                vec![source_edge]
+            } else {
+                vec![source_edge, target_edge]
+            })
+        })
+        .flat_map(|result| match result {
+            Err(err) => vec![Err(err)],
+            Ok(ok) => ok.into_iter().map(Ok).collect(),
+        })
+        .try_collect()?;
+
+    info!("finding points");
+
+    // only nodes the bike can reach are worth starting from
+    let origin_point = Point::from(area.centre);
+    let origin_node_id = nodes
+        .iter()
+        .filter(|(node_id, _)| gradients.contains_node(**node_id))
+        .map(|(node_id, (coord, _))| (*node_id, *coord))
+        .fold(None::<(i64, f64)>, |accu, (next_node_id, coord)| {
+            let next_distance = Haversine::distance(origin_point, coord.into());
+
+            accu.filter(|(_, prev_distance)| &next_distance > prev_distance)
+                .or(Some((next_node_id, next_distance)))
+        })
+        .ok_or_else(|| anyhow!("Expected to find the closest node_id to the origin"))?
+        .0;
+
+    // the highest node we can ride up to from the origin and back down again
+    let ascendable = reachable(&gradients, origin_node_id);
+    let descendable = reachable(Reversed(&gradients), origin_node_id);
+    let highest_node_id = *nodes
+        .keys()
+        .find(|node_id| ascendable.contains(node_id) && descendable.contains(node_id))
+        .ok_or_else(|| anyhow!("Expected to find the highest node_id"))?;
+
+    info!("finding path ascent");
+    let ascent_model = CostModel::ascent();
+    let (_, ascent) = shortest_path(
+        &gradients,
+        origin_node_id,
+        highest_node_id,
+        None,
+        |(_source_node_id, _target_node_id, edge)| {
+            ascent_model.cost(&edge.segment) * surface_penalty(edge.way_id).unwrap_or(1.0)
+        },
+        can_turn,
+    )?;
+
+    info!("finding path descent");
+    // we want some decline but not full decline
+    // punish when decline is too high
+    let descent_model = CostModel::descent();
+    // turning around at the summit has to follow the restrictions too
+    let summit_edge = ascent.windows(2).last().map(|edge| (edge[0], edge[1]));
+    let (_, descent) = shortest_path(
+        &gradients,
+        highest_node_id,
+        origin_node_id,
+        summit_edge,
+        |(_source_node_id, _target_node_id, edge)| {
+            descent_model.cost(&edge.segment) * surface_penalty(edge.way_id).unwrap_or(1.0)
+        },
+        can_turn,
+    )?;
+
+    Ok(Circuit {
+        nodes,
+        gradients,
+        ways,
+        ascent,
+        descent,
+    })
+}
+
+/// Node ids that can be reached by following the edges of the graph from the start.
+fn reachable<G>(graph: G, start: i64) -> HashSet<i64>
+where
+    G: IntoNeighbors<NodeId = i64> + Visitable,
+{
+    let mut dfs = Dfs::new(graph, start);
+    let mut node_ids = HashSet::new();
+    while let Some(node_id) = dfs.next(graph) {
+        node_ids.insert(node_id);
+    }
+    node_ids
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        area::SearchArea,
+        bootstrap::{insert_coordinates, insert_elevations, insert_ways},
+        circuit::find_circuit,
+        cost::BikeProfile,
+        osm::{CyclableWays, WayEdge, WayTags},
+        store::memory::MemoryStore,
+        turns::TurnRestriction,
+    };
+    use geo::{Coord, Rect};
+    use itertools::Itertools;
+    use petgraph::prelude::DiGraphMap;
+    use std::collections::HashMap;
+
+    const TWO_WAY: WayEdge = WayEdge {
+        oneway: false,
+        way_id: Some(100),
+    };
+    const ONEWAY: WayEdge = WayEdge {
+        oneway: true,
+        way_id: Some(200),
+    };
+    const DIRT: WayEdge = WayEdge {
+        oneway: false,
+        way_id: Some(300),
+    };
+    const SUMMIT: WayEdge = WayEdge {
+        oneway: false,
+        way_id: Some(400),
+    };
+
+    /// A small neighbourhood on a hill that rises 100 metres every 0.01 degrees north.
+    fn fixture(edges: &[(i64, i64, WayEdge)]) -> (CyclableWays, HashMap<i64, Coord>) {
+        let coords = HashMap::from([
+            (1, Coord { x: 0.0, y: 0.0 }),
+            (2, Coord { x: 0.005, y: 0.005 }),
+            (
+                3,
+                Coord {
+                    x: -0.005,
+                    y: 0.005,
+                },
+            ),
+            (4, Coord { x: 0.0, y: 0.01 }),
+            (5, Coord { x: 0.0, y: -0.005 }),
+            // beyond the search area
+            (6, Coord { x: 1.0, y: 1.0 }),
+        ]);
+
+        let ways = HashMap::from([
+            (
+                100,
+                WayTags {
+                    highway: Some("residential".to_string()),
+                    name: Some("Hill Street".to_string()),
+                    ..WayTags::default()
+                },
+            ),
+            (
+                200,
+                WayTags {
+                    highway: Some("tertiary".to_string()),
+                    ..WayTags::default()
+                },
+            ),
+            (
+                300,
+                WayTags {
+                    highway: Some("service".to_string()),
+                    surface: Some("dirt".to_string()),
+                    ..WayTags::default()
+                },
+            ),
+            (
+                400,
+                WayTags {
+                    highway: Some("residential".to_string()),
+                    name: Some("Summit Road".to_string()),
+                    ..WayTags::default()
+                },
+            ),
+        ]);
+
+        let graph = DiGraphMap::from_edges(edges);
+        let restrictions = Vec::new();
+
+        (
+            CyclableWays {
+                graph,
+                ways,
+                restrictions,
+                ..CyclableWays::default()
+            },
+            coords,
+        )
+    }
+
+    async fn bootstrap(cyclable: CyclableWays, coords: &HashMap<i64, Coord>) -> MemoryStore {
+        let store = MemoryStore::default();
+
+        insert_ways(&store, cyclable).await.unwrap();
+
+        insert_coordinates(&store, |node_ids| {
+            Ok(coords
+                .iter()
+                .filter(|(node_id, _)| node_ids.contains(node_id))
+                .map(|(node_id, coord)| (*node_id, *coord))
+                .collect())
+        })
+        .await
+        .unwrap();
+
+        let rect = Rect::new(Coord { x: -2.0, y: -2.0 }, Coord { x: 2.0, y: 2.0 });
+        insert_elevations(&store, rect, |coord| Ok(coord.y * 10_000.0))
+            .await
+            .unwrap();
+
+        store
+    }
+
+    #[tokio::test]
+    async fn bootstrap_then_circuit() {
+        let (cyclable, coords) = fixture(&[
+            (1, 2, TWO_WAY),
+            (2, 4, TWO_WAY),
+            (1, 3, TWO_WAY),
+            (3, 4, TWO_WAY),
+            (1, 5, TWO_WAY),
+            (4, 6, TWO_WAY),
+        ]);
+        let store = bootstrap(cyclable, &coords).await;
+
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+        let circuit = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+
+        assert!(!circuit.nodes.contains_key(&6));
+        assert_eq!(circuit.ascent.first(), Some(&1));
+        assert_eq!(circuit.ascent.last(), Some(&4));
+        assert_eq!(circuit.descent.first(), Some(&4));
+        assert_eq!(circuit.descent.last(), Some(&1));
+
+        for (source, target) in circuit.node_ids().tuple_windows() {
+            let edge = circuit.gradients.edge_weight(source, target).unwrap();
+            assert!(circuit.ways.contains_key(&edge.way_id.unwrap()));
+        }
+
+        assert_eq!(circuit.ways[&100].name.as_deref(), Some("Hill Street"));
+    }
+
+    #[tokio::test]
+    async fn never_rides_against_oneway() {
+        // the direct road from the origin to the top is a oneway descent.
+        let (cyclable, coords) = fixture(&[(4, 1, ONEWAY), (1, 2, TWO_WAY), (2, 4, TWO_WAY)]);
+        let store = bootstrap(cyclable, &coords).await;
+
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+        let circuit = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+
+        assert!(!circuit.gradients.contains_edge(1, 4));
+        assert_eq!(circuit.ascent, vec![1, 2, 4]);
+        assert_eq!(circuit.descent.last(), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn profile_decides_the_ground() {
+        // the direct road to the top is dirt, the long way round is paved.
+        let (cyclable, coords) = fixture(&[
+            (1, 4, DIRT),
+            (1, 2, TWO_WAY),
+            (2, 4, TWO_WAY),
+            (1, 5, TWO_WAY),
+        ]);
+        let store = bootstrap(cyclable, &coords).await;
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+
+        let road = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+        assert!(!road.gradients.contains_edge(1, 4));
+        assert_eq!(road.ascent, vec![1, 2, 4]);
+
+        let mtb = find_circuit(&store, &area, BikeProfile::Mtb).await.unwrap();
+        assert_eq!(mtb.ascent, vec![1, 4]);
+    }
+
+    #[tokio::test]
+    async fn never_takes_restricted_turns() {
+        // both ways to the top are as long, but turning onto Summit Road at 2 is forbidden.
+        let (mut cyclable, coords) = fixture(&[
+            (1, 2, TWO_WAY),
+            (2, 4, SUMMIT),
+            (1, 3, TWO_WAY),
+            (3, 4, TWO_WAY),
+            (1, 5, TWO_WAY),
+        ]);
+        cyclable.restrictions.push(TurnRestriction {
+            relation_id: 1,
+            from_way_id: 100,
+            via_node_id: 2,
+            to_way_id: 400,
+            only: false,
+        });
+        let store = bootstrap(cyclable, &coords).await;
+
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+        let circuit = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+
+        assert_eq!(circuit.ascent, vec![1, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn turns_around_at_the_summit_by_the_restrictions() {
+        // Summit Road is the only way up, and turning back down it at the top is forbidden.
+        let (mut cyclable, coords) = fixture(&[
+            (1, 2, TWO_WAY),
+            (2, 4, SUMMIT),
+            (4, 3, ONEWAY),
+            (1, 3, TWO_WAY),
+            (1, 5, TWO_WAY),
+        ]);
+        cyclable.restrictions.push(TurnRestriction {
+            relation_id: 1,
+            from_way_id: 400,
+            via_node_id: 4,
+            to_way_id: 400,
+            only: false,
+        });
+        let store = bootstrap(cyclable, &coords).await;
+
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+        let circuit = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+
+        assert_eq!(circuit.ascent, vec![1, 2, 4]);
+        assert_eq!(circuit.descent, vec![4, 3, 1]);
+    }
+
+    #[tokio::test]
+    async fn summit_is_reachable_by_the_profile() {
+        // the top can only be reached by dirt, so a road bike stops short of it.
+        let (cyclable, coords) = fixture(&[(1, 2, TWO_WAY), (2, 4, DIRT), (1, 5, TWO_WAY)]);
+        let store = bootstrap(cyclable, &coords).await;
+        let area = SearchArea::from_kilometres(0.0001, 0.0001, 2.0);
+
+        let road = find_circuit(&store, &area, BikeProfile::Road)
+            .await
+            .unwrap();
+        assert_eq!(road.ascent, vec![1, 2]);
+        assert_eq!(road.descent, vec![2, 1]);
+
+        let mtb = find_circuit(&store, &area, BikeProfile::Mtb).await.unwrap();
+        assert_eq!(mtb.ascent, vec![1, 2, 4]);
+    }
+}
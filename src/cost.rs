@@ -0,0 +1,300 @@
+use crate::{osm::WayTags, segment::Segment};
+use clap::ValueEnum;
+
+/// Weighs segments for the routing search.
+///
+/// Costs are metres ridden, inflated the further a segment's gradient strays
+/// from the preferred gradient, so they are never negative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostModel {
+    /// Gradient that costs no more than its distance.
+    pub preferred_gradient: f64,
+    /// Extra metres charged per metre ridden for each unit of gradient
+    /// away from the preferred gradient.
+    pub gradient_penalty: f64,
+}
+
+impl CostModel {
+    /// Climbs as gently as possible, avoiding both steep ramps and
+    /// descents that have to be climbed again.
+    pub fn ascent() -> Self {
+        Self {
+            preferred_gradient: 0.0,
+            gradient_penalty: 20.0,
+        }
+    }
+
+    /// Prefers a steady decline, punishing roads that are too steep
+    /// or that climb.
+    pub fn descent() -> Self {
+        Self {
+            preferred_gradient: -0.04,
+            gradient_penalty: 20.0,
+        }
+    }
+
+    /// Climbs and descents that cancel out along a segment, like crossing a valley,
+    /// are charged as metres ridden away from the preferred gradient too.
+    pub fn cost(&self, segment: &Segment) -> f64 {
+        let deviation = (segment.gradient - self.preferred_gradient).abs();
+        let undulation =
+            segment.climbing + segment.descending - (segment.gradient * segment.distance).abs();
+
+        segment.distance * (1.0 + self.gradient_penalty * deviation)
+            + self.gradient_penalty * undulation.max(0.0)
+    }
+}
+
+/// The kind of bike being ridden, which decides the ground it can cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum BikeProfile {
+    /// Narrow tyres that want paved roads.
+    #[default]
+    Road,
+    /// Wider tyres that cope with tracks, but not mud or sand.
+    Gravel,
+    /// Rides almost anything.
+    Mtb,
+}
+
+/// Coarse classes of the `surface` tag, from smoothest to softest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ground {
+    Paved,
+    Cobbled,
+    Compacted,
+    Loose,
+    Dirt,
+    Soft,
+}
+
+impl Ground {
+    /// Inferred from https://wiki.openstreetmap.org/wiki/Key:surface
+    fn from_surface(surface: &str) -> Option<Self> {
+        let ground = match surface {
+            "asphalt" | "concrete" | "paved" | "chipseal" | "paving_stones" | "metal" => {
+                Ground::Paved
+            }
+            "sett" | "cobblestone" | "unhewn_cobblestone" | "bricks" | "wood" => Ground::Cobbled,
+            "compacted" | "fine_gravel" => Ground::Compacted,
+            "gravel" | "unpaved" | "pebblestone" | "rock" => Ground::Loose,
+            "dirt" | "ground" | "earth" | "grass" | "woodchips" => Ground::Dirt,
+            "sand" | "mud" => Ground::Soft,
+            surface if surface.starts_with("concrete:") => Ground::Paved,
+            _ => return None,
+        };
+
+        Some(ground)
+    }
+}
+
+impl BikeProfile {
+    /// Multiplies the cost of riding a way by how badly its surface suits the bike,
+    /// or returns `None` when the bike can't ride it at all.
+    ///
+    /// Missing or unknown tags are assumed to be fine.
+    pub fn surface_penalty(&self, tags: &WayTags) -> Option<f64> {
+        let surface = tags
+            .surface
+            .as_deref()
+            .and_then(Ground::from_surface)
+            .map_or(Some(1.0), |ground| self.ground_penalty(ground))?;
+
+        // https://wiki.openstreetmap.org/wiki/Key:tracktype
+        let tracktype = match tags.tracktype.as_deref() {
+            Some("grade1") => 0,
+            Some("grade2") => 1,
+            Some("grade3") => 2,
+            Some("grade4") => 3,
+            Some("grade5") => 4,
+            _ => 0,
+        };
+
+        // https://wiki.openstreetmap.org/wiki/Key:smoothness
+        let smoothness = match tags.smoothness.as_deref() {
+            Some("excellent") => 0,
+            Some("good") => 1,
+            Some("intermediate") => 2,
+            Some("bad") => 3,
+            Some("very_bad") => 4,
+            Some("horrible") => 5,
+            Some("very_horrible") => 6,
+            Some("impassable") => 7,
+            _ => 0,
+        };
+
+        let tracktype = self.tracktype_penalties()[tracktype]?;
+        let smoothness = self.smoothness_penalties()[smoothness]?;
+
+        Some(surface * tracktype * smoothness)
+    }
+
+    fn ground_penalty(&self, ground: Ground) -> Option<f64> {
+        match (self, ground) {
+            (_, Ground::Paved) => Some(1.0),
+            (BikeProfile::Road, Ground::Cobbled) => Some(1.5),
+            (BikeProfile::Road, Ground::Compacted) => Some(2.0),
+            (BikeProfile::Road, Ground::Loose) => Some(4.0),
+            (BikeProfile::Road, Ground::Dirt | Ground::Soft) => None,
+            (BikeProfile::Gravel, Ground::Cobbled | Ground::Loose) => Some(1.2),
+            (BikeProfile::Gravel, Ground::Compacted) => Some(1.0),
+            (BikeProfile::Gravel, Ground::Dirt) => Some(1.5),
+            (BikeProfile::Gravel, Ground::Soft) => Some(4.0),
+            (BikeProfile::Mtb, Ground::Cobbled) => Some(1.2),
+            (BikeProfile::Mtb, Ground::Compacted | Ground::Loose | Ground::Dirt) => Some(1.0),
+            (BikeProfile::Mtb, Ground::Soft) => Some(2.0),
+        }
+    }
+
+    /// Penalties for `grade1` to `grade5`.
+    fn tracktype_penalties(&self) -> [Option<f64>; 5] {
+        match self {
+            BikeProfile::Road => [Some(1.0), Some(2.0), None, None, None],
+            BikeProfile::Gravel => [Some(1.0), Some(1.0), Some(1.2), Some(2.0), Some(4.0)],
+            BikeProfile::Mtb => [Some(1.0), Some(1.0), Some(1.0), Some(1.2), Some(1.5)],
+        }
+    }
+
+    /// Penalties for `excellent` to `impassable`.
+    fn smoothness_penalties(&self) -> [Option<f64>; 8] {
+        match self {
+            BikeProfile::Road => [
+                Some(1.0),
+                Some(1.0),
+                Some(1.2),
+                Some(3.0),
+                None,
+                None,
+                None,
+                None,
+            ],
+            BikeProfile::Gravel => [
+                Some(1.0),
+                Some(1.0),
+                Some(1.0),
+                Some(1.3),
+                Some(2.0),
+                None,
+                None,
+                None,
+            ],
+            BikeProfile::Mtb => [
+                Some(1.0),
+                Some(1.0),
+                Some(1.0),
+                Some(1.0),
+                Some(1.2),
+                Some(2.0),
+                Some(4.0),
+                None,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        cost::{BikeProfile, CostModel},
+        osm::WayTags,
+        segment::Segment,
+    };
+
+    fn segment(gradient: f64) -> Segment {
+        Segment {
+            distance: 100.0,
+            gradient,
+            climbing: (gradient * 100.0).max(0.0),
+            descending: (-gradient * 100.0).max(0.0),
+            max_gradient: gradient,
+            min_gradient: gradient,
+        }
+    }
+
+    #[test]
+    fn costs_are_never_negative() {
+        for model in [CostModel::ascent(), CostModel::descent()] {
+            for gradient in [-1.0, -0.2, -0.04, 0.0, 0.04, 0.2, 1.0] {
+                assert!(model.cost(&segment(gradient)) >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn ascent_prefers_gentle_climbs() {
+        let model = CostModel::ascent();
+
+        assert!(model.cost(&segment(0.03)) < model.cost(&segment(0.12)));
+        assert!(model.cost(&segment(0.0)) < model.cost(&segment(-0.05)));
+    }
+
+    #[test]
+    fn descent_prefers_steady_declines() {
+        let model = CostModel::descent();
+
+        assert!(model.cost(&segment(-0.04)) < model.cost(&segment(-0.15)));
+        assert!(model.cost(&segment(-0.04)) < model.cost(&segment(0.02)));
+    }
+
+    #[test]
+    fn valleys_cost_more_than_their_gradient() {
+        let valley = Segment {
+            climbing: 12.0,
+            descending: 10.0,
+            min_gradient: -0.2,
+            max_gradient: 0.24,
+            ..segment(0.02)
+        };
+
+        for model in [CostModel::ascent(), CostModel::descent()] {
+            assert!(model.cost(&valley) > model.cost(&segment(0.02)));
+        }
+    }
+
+    fn surface(surface: &str) -> WayTags {
+        WayTags {
+            surface: Some(surface.to_string()),
+            ..WayTags::default()
+        }
+    }
+
+    #[test]
+    fn untagged_ways_are_not_penalised() {
+        for profile in [BikeProfile::Road, BikeProfile::Gravel, BikeProfile::Mtb] {
+            assert_eq!(profile.surface_penalty(&WayTags::default()), Some(1.0));
+            assert_eq!(profile.surface_penalty(&surface("asphalt")), Some(1.0));
+        }
+    }
+
+    #[test]
+    fn road_bikes_avoid_unpaved_ground() {
+        let road = BikeProfile::Road;
+
+        assert!(road.surface_penalty(&surface("gravel")).unwrap() > 1.0);
+        assert_eq!(road.surface_penalty(&surface("dirt")), None);
+        assert_eq!(road.surface_penalty(&surface("sand")), None);
+
+        let track = WayTags {
+            tracktype: Some("grade3".to_string()),
+            ..WayTags::default()
+        };
+        assert_eq!(road.surface_penalty(&track), None);
+    }
+
+    #[test]
+    fn rougher_bikes_ride_rougher_ground() {
+        let dirt = surface("dirt");
+
+        assert!(
+            BikeProfile::Mtb.surface_penalty(&dirt).unwrap()
+                < BikeProfile::Gravel.surface_penalty(&dirt).unwrap()
+        );
+
+        let horrible = WayTags {
+            smoothness: Some("horrible".to_string()),
+            ..WayTags::default()
+        };
+        assert_eq!(BikeProfile::Gravel.surface_penalty(&horrible), None);
+        assert!(BikeProfile::Mtb.surface_penalty(&horrible).is_some());
+    }
+}
@@ -0,0 +1,589 @@
+use crate::elevation::{
+    asc::read_asc,
+    hgt::read_hgt,
+    projection::{Projection, Reprojected},
+};
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, ValueEnum};
+use geo::{Coord, Rect};
+use geotiff::{GeoKeyDirectory, RasterType};
+use num_traits::ToPrimitive;
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    tags::Tag,
+};
+
+mod asc;
+mod hgt;
+mod projection;
+
+/// How an elevation is estimated between the centres of raster pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Interpolation {
+    /// The value of the pixel containing the coordinate, which steps at every pixel edge.
+    Nearest,
+    /// Weighted between the four surrounding pixels.
+    #[default]
+    Bilinear,
+    /// Catmull-Rom spline through the sixteen surrounding pixels, smooth across pixel edges.
+    Bicubic,
+}
+
+/// Rasters to read elevations from and how to sample them.
+#[derive(Debug, Args, Clone)]
+pub struct RasterArgs {
+    /// GeoTIFF (`.tif`), SRTM (`.hgt`) or Esri ASCII grid (`.asc`) files, which may be mixed.
+    pub rasters: Vec<PathBuf>,
+
+    /// How elevations are estimated between the centres of pixels.
+    #[arg(long, value_enum, default_value_t = Interpolation::Bilinear)]
+    pub interpolation: Interpolation,
+}
+
+/// Why a coordinate has no elevation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Missing {
+    /// The coordinate isn't covered by the raster.
+    Outside,
+    /// A pixel needed for the elevation holds the NoData value.
+    NoData,
+}
+
+impl Interpolation {
+    /// How much each of the four nearest pixels along an axis contributes, from the one
+    /// before the preceding pixel centre to the one after the following centre,
+    /// with `t` being the fraction of the way between those centres.
+    fn weights(&self, t: f64) -> [f64; 4] {
+        match self {
+            Interpolation::Nearest if t < 0.5 => [0.0, 1.0, 0.0, 0.0],
+            Interpolation::Nearest => [0.0, 0.0, 1.0, 0.0],
+            Interpolation::Bilinear => [0.0, 1.0 - t, t, 0.0],
+            Interpolation::Bicubic => {
+                let (t2, t3) = (t * t, t * t * t);
+
+                [
+                    0.5 * (-t + 2.0 * t2 - t3),
+                    0.5 * (2.0 - 5.0 * t2 + 3.0 * t3),
+                    0.5 * (t + 4.0 * t2 - 3.0 * t3),
+                    0.5 * (t3 - t2),
+                ]
+            }
+        }
+    }
+}
+
+/// Anything elevations can be sampled from, so rasters of different formats can be mixed.
+pub trait ElevationSource {
+    /// The area covered, in model coordinates.
+    fn extent(&self) -> Rect;
+
+    /// Estimates the elevation at a model coordinate.
+    fn sample(&self, coord: &Coord, interpolation: Interpolation) -> Result<f64, Missing>;
+}
+
+/// Reads a GeoTIFF, SRTM `.hgt` tile or Esri `.asc` grid, recognised by its extension.
+///
+/// GeoTIFFs in a projected CRS are sampled through their projection, while the other
+/// formats are always in longitude and latitude.
+pub fn read_elevation_source(path: &Path) -> Result<Box<dyn ElevationSource>> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let (raster, projection) = match extension.as_deref() {
+        Some("tif" | "tiff") => Raster::read_geotiff(path)?,
+        Some("hgt") => (read_hgt(path)?, Projection::Geographic),
+        Some("asc") => (read_asc(path)?, Projection::Geographic),
+        _ => {
+            return Err(anyhow!(
+                "Expected a .tif, .hgt or .asc elevation file, found {:?}",
+                path
+            ))
+        }
+    };
+
+    Ok(match projection {
+        Projection::Geographic => Box::new(raster),
+        projection => Box::new(Reprojected {
+            source: raster,
+            projection,
+        }),
+    })
+}
+
+/// A grid of elevations whose pixels are aligned with the model's axes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    /// Model coordinate of the centre of the first pixel.
+    pub origin: Coord,
+    /// Model units from one pixel centre to the next, negative when rows go southwards.
+    pub pixel_size: Coord,
+    /// Row major values, where `NaN` marks pixels without data.
+    pub values: Vec<f32>,
+}
+
+impl Raster {
+    /// Reads a GeoTIFF along with its CRS, treating pixels equal to its GDAL NoData tag as missing.
+    ///
+    /// The image is decoded once and the first sample of each pixel converted as a whole,
+    /// keeping the order of the file as long as it isn't rotated.
+    pub fn read_geotiff(path: &Path) -> Result<(Self, Projection)> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+
+        let no_data = read_no_data(&mut decoder)
+            .with_context(|| format!("Expected a readable NoData tag in {:?}", path))?;
+        let keys = read_geo_keys(&mut decoder)
+            .with_context(|| format!("Expected readable GeoKeys in {:?}", path))?;
+        let projection = Projection::from_geo_keys(&keys)
+            .with_context(|| format!("Expected a supported CRS in {:?}", path))?;
+
+        let (width, height) = decoder.dimensions()?;
+        let (width, height) = (width as usize, height as usize);
+        let samples = match decoder.find_tag(Tag::SamplesPerPixel)? {
+            Some(samples) => samples.into_u16()? as usize,
+            None => 1,
+        };
+
+        // integer raster coordinates are pixel centres rather than corners for PixelIsPoint
+        let offset = match keys.raster_type {
+            Some(RasterType::RasterPixelIsPoint) => 0.0,
+            _ => 0.5,
+        };
+        let (origin, pixel_size) = read_model_transform(&mut decoder, offset)
+            .with_context(|| format!("Expected a north up model transform in {:?}", path))?;
+
+        let values = first_samples(decoder.read_image()?, samples, no_data);
+
+        Ok((
+            Self {
+                width,
+                height,
+                origin,
+                pixel_size,
+                values,
+            },
+            projection,
+        ))
+    }
+
+    /// The value of a pixel, repeating the edges for pixels outside the raster.
+    fn value(&self, column: isize, row: isize) -> f64 {
+        let column = column.clamp(0, self.width as isize - 1) as usize;
+        let row = row.clamp(0, self.height as isize - 1) as usize;
+
+        self.values[row * self.width + column] as f64
+    }
+}
+
+impl ElevationSource for Raster {
+    fn extent(&self) -> Rect {
+        let first = self.origin - self.pixel_size / 2.0;
+        let last = Coord {
+            x: first.x + self.width as f64 * self.pixel_size.x,
+            y: first.y + self.height as f64 * self.pixel_size.y,
+        };
+
+        Rect::new(first, last)
+    }
+
+    /// Pixels beyond the edges repeat the edge, so coordinates within half a pixel
+    /// of the border can still be interpolated.
+    /// Elevations that need any pixel without data are missing rather than guessed.
+    fn sample(&self, coord: &Coord, interpolation: Interpolation) -> Result<f64, Missing> {
+        let column = (coord.x - self.origin.x) / self.pixel_size.x;
+        let row = (coord.y - self.origin.y) / self.pixel_size.y;
+
+        let outside =
+            |position: f64, length: usize| !(-0.5..length as f64 - 0.5).contains(&position);
+
+        if outside(column, self.width) || outside(row, self.height) {
+            return Err(Missing::Outside);
+        }
+
+        let (left, top) = (column.floor(), row.floor());
+        let columns = interpolation.weights(column - left);
+        let rows = interpolation.weights(row - top);
+
+        // pixels that don't contribute can't spoil the elevation with a lack of data
+        let elevation = (0..4)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|(x, y)| columns[*x] * rows[*y] != 0.0)
+            .map(|(x, y)| {
+                let value = self.value(
+                    left as isize + x as isize - 1,
+                    top as isize + y as isize - 1,
+                );
+                columns[x] * rows[y] * value
+            })
+            .sum::<f64>();
+
+        if elevation.is_nan() {
+            Err(Missing::NoData)
+        } else {
+            Ok(elevation)
+        }
+    }
+}
+
+/// GDAL stores the NoData value as text, such as `-32768` for SRTM.
+/// https://gdal.org/en/stable/drivers/raster/gtiff.html#nodata-value
+fn read_no_data(decoder: &mut Decoder<impl Read + Seek>) -> Result<Option<f64>> {
+    let Some(value) = decoder.find_tag(Tag::GdalNodata)? else {
+        return Ok(None);
+    };
+
+    let text = value.into_string()?;
+    let no_data = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+
+    Ok(Some(no_data.parse()?))
+}
+
+/// Reads the GeoKeys that `Projection::from_geo_keys` and the raster type depend on,
+/// leaving the rest of the directory empty.
+/// https://docs.ogc.org/is/19-008r4/19-008r4.html#_requirements_class_geokeydirectorytag
+fn read_geo_keys(decoder: &mut Decoder<impl Read + Seek>) -> Result<GeoKeyDirectory> {
+    let mut keys = GeoKeyDirectory::default();
+
+    let Some(directory) = decoder.find_tag(Tag::GeoKeyDirectoryTag)? else {
+        return Ok(keys);
+    };
+    let directory = directory.into_u16_vec()?;
+    let doubles = match decoder.find_tag(Tag::GeoDoubleParamsTag)? {
+        Some(doubles) => doubles.into_f64_vec()?,
+        None => Vec::new(),
+    };
+
+    // each key is its id, where its value is stored, how many values and the value or offset
+    for key in directory.get(4..).unwrap_or_default().chunks_exact(4) {
+        let short = (key[1] == 0).then_some(key[3]);
+        let double = (key[1] == Tag::GeoDoubleParamsTag.to_u16())
+            .then(|| doubles.get(key[3] as usize).copied())
+            .flatten();
+
+        match key[0] {
+            1024 => keys.model_type = short,
+            1025 => keys.raster_type = short.and_then(|short| RasterType::try_from(short).ok()),
+            2057 => keys.geog_semi_major_axis = double,
+            2059 => keys.geog_inv_flattening = double,
+            3072 => keys.projected_type = short,
+            3074 => keys.projection = short,
+            3075 => keys.proj_coord_trans = short,
+            3076 => keys.proj_linear_units = short,
+            3080 => keys.proj_nat_origin_long = double,
+            3081 => keys.proj_nat_origin_lat = double,
+            3082 => keys.proj_false_easting = double,
+            3083 => keys.proj_false_northing = double,
+            3092 => keys.proj_scale_at_nat_origin = double,
+            _ => {}
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Reads the centre of the first pixel and the size of each pixel in model coordinates,
+/// from either a tie point and pixel scale or a transformation without rotation.
+///
+/// `offset` is where the centre of a pixel lies in raster coordinates.
+fn read_model_transform(
+    decoder: &mut Decoder<impl Read + Seek>,
+    offset: f64,
+) -> Result<(Coord, Coord)> {
+    let mut f64_tag = |tag| -> Result<Option<Vec<f64>>> {
+        Ok(decoder
+            .find_tag(tag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()?)
+    };
+
+    let scale = f64_tag(Tag::ModelPixelScaleTag)?;
+    let tie_point = f64_tag(Tag::ModelTiepointTag)?;
+    let transformation = f64_tag(Tag::ModelTransformationTag)?;
+
+    match (
+        scale.as_deref(),
+        tie_point.as_deref(),
+        transformation.as_deref(),
+    ) {
+        (Some([scale_x, scale_y, ..]), Some([i, j, _, x, y, ..]), _) => Ok((
+            Coord {
+                x: x + (offset - i) * scale_x,
+                y: y - (offset - j) * scale_y,
+            },
+            Coord {
+                x: *scale_x,
+                y: -scale_y,
+            },
+        )),
+        (_, _, Some([a, 0.0, _, d, 0.0, f, _, h, ..])) => Ok((
+            Coord {
+                x: d + offset * a,
+                y: h + offset * f,
+            },
+            Coord { x: *a, y: *f },
+        )),
+        _ => Err(anyhow!(
+            "Expected a tie point with a pixel scale, or a transformation without rotation"
+        )),
+    }
+}
+
+/// Converts the first sample of every pixel to `f32`, with NaN where there's no data.
+fn first_samples(image: DecodingResult, samples: usize, no_data: Option<f64>) -> Vec<f32> {
+    let no_data = no_data.map(|no_data| no_data as f32);
+
+    fn convert<T: ToPrimitive>(data: Vec<T>, samples: usize, no_data: Option<f32>) -> Vec<f32> {
+        data.into_iter()
+            .step_by(samples)
+            .map(|value| value.to_f32().filter(|value| Some(*value) != no_data))
+            .map(|value| value.unwrap_or(f32::NAN))
+            .collect()
+    }
+
+    match image {
+        // already the right type, so it's marked in place instead of copied
+        DecodingResult::F32(mut data) if samples == 1 => {
+            for value in data.iter_mut().filter(|value| Some(**value) == no_data) {
+                *value = f32::NAN;
+            }
+            data
+        }
+        DecodingResult::F32(data) => convert(data, samples, no_data),
+        DecodingResult::F64(data) => convert(data, samples, no_data),
+        DecodingResult::U8(data) => convert(data, samples, no_data),
+        DecodingResult::U16(data) => convert(data, samples, no_data),
+        DecodingResult::U32(data) => convert(data, samples, no_data),
+        DecodingResult::U64(data) => convert(data, samples, no_data),
+        DecodingResult::I8(data) => convert(data, samples, no_data),
+        DecodingResult::I16(data) => convert(data, samples, no_data),
+        DecodingResult::I32(data) => convert(data, samples, no_data),
+        DecodingResult::I64(data) => convert(data, samples, no_data),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::elevation::{ElevationSource, Interpolation, Missing, Projection, Raster};
+    use geo::Coord;
+    use std::fs::File;
+    use tiff::{
+        encoder::colortype::{Gray32Float, GrayI16},
+        encoder::TiffEncoder,
+        tags::Tag,
+    };
+
+    /// 4x3 pixels of 1 degree each, with the top left corner at (10, 20).
+    fn raster(value: impl Fn(f64, f64) -> f64) -> Raster {
+        let (width, height) = (4, 3);
+        let origin = Coord { x: 10.5, y: 19.5 };
+        let pixel_size = Coord { x: 1.0, y: -1.0 };
+
+        let values = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                value(
+                    origin.x + column as f64 * pixel_size.x,
+                    origin.y + row as f64 * pixel_size.y,
+                ) as f32
+            })
+            .collect();
+
+        Raster {
+            width,
+            height,
+            origin,
+            pixel_size,
+            values,
+        }
+    }
+
+    fn assert_close(actual: Result<f64, Missing>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, found {actual}"
+        );
+    }
+
+    #[test]
+    fn covers_the_pixels() {
+        let extent = raster(|_, _| 0.0).extent();
+
+        assert_eq!(extent.min(), Coord { x: 10.0, y: 17.0 });
+        assert_eq!(extent.max(), Coord { x: 14.0, y: 20.0 });
+    }
+
+    #[test]
+    fn nearest_steps_between_pixels() {
+        let raster = raster(|x, y| x * 10.0 + y);
+
+        assert_close(
+            raster.sample(&Coord { x: 10.9, y: 19.1 }, Interpolation::Nearest),
+            124.5,
+        );
+        assert_close(
+            raster.sample(&Coord { x: 11.1, y: 19.1 }, Interpolation::Nearest),
+            134.5,
+        );
+    }
+
+    #[test]
+    fn bilinear_is_exact_on_planes() {
+        let plane = |x: f64, y: f64| x * 10.0 - y * 3.0 + 7.0;
+        let raster = raster(plane);
+
+        for coord in [
+            Coord { x: 10.5, y: 19.5 },
+            Coord { x: 11.2, y: 18.7 },
+            Coord { x: 13.4, y: 17.6 },
+        ] {
+            assert_close(
+                raster.sample(&coord, Interpolation::Bilinear),
+                plane(coord.x, coord.y),
+            );
+        }
+    }
+
+    #[test]
+    fn bicubic_is_exact_on_curves_away_from_edges() {
+        let curve = |x: f64, y: f64| x * x * 2.0 + y;
+        let raster = raster(curve);
+        let coord = Coord { x: 12.25, y: 18.5 };
+
+        assert_close(
+            raster.sample(&coord, Interpolation::Bicubic),
+            curve(coord.x, coord.y),
+        );
+        // straight lines between pixels overestimate a curve bending upwards
+        assert!(raster.sample(&coord, Interpolation::Bilinear).unwrap() > curve(coord.x, coord.y));
+    }
+
+    #[test]
+    fn interpolates_pixel_centres_exactly() {
+        let raster = raster(|x, y| (x * y).sin() * 100.0);
+        let centre = Coord { x: 12.5, y: 18.5 };
+        let expected = (12.5f64 * 18.5).sin() * 100.0;
+
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+        ] {
+            assert_close(raster.sample(&centre, interpolation), expected);
+        }
+    }
+
+    #[test]
+    fn nothing_outside_the_raster() {
+        let raster = raster(|_, _| 1.0);
+
+        assert_eq!(
+            raster.sample(&Coord { x: 9.9, y: 19.0 }, Interpolation::Bilinear),
+            Err(Missing::Outside)
+        );
+        assert_eq!(
+            raster.sample(&Coord { x: 12.0, y: 20.1 }, Interpolation::Bicubic),
+            Err(Missing::Outside)
+        );
+        assert_close(
+            raster.sample(&Coord { x: 13.9, y: 17.1 }, Interpolation::Bicubic),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn no_data_is_missing_rather_than_interpolated() {
+        let mut raster = raster(|_, _| 100.0);
+        // the pixel centred on (12.5, 18.5)
+        raster.values[6] = f32::NAN;
+
+        let next_to_the_hole = Coord { x: 11.8, y: 18.5 };
+        let far_away = Coord { x: 10.5, y: 17.5 };
+
+        assert_eq!(
+            raster.sample(&next_to_the_hole, Interpolation::Bilinear),
+            Err(Missing::NoData)
+        );
+        assert_close(
+            raster.sample(&next_to_the_hole, Interpolation::Nearest),
+            100.0,
+        );
+        assert_close(raster.sample(&far_away, Interpolation::Bicubic), 100.0);
+    }
+
+    #[test]
+    fn reads_the_no_data_tag() {
+        let path = std::env::temp_dir().join("elevated-cycling-no-data.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder.new_image::<Gray32Float>(2, 2).unwrap();
+
+        let tags = image.encoder();
+        tags.write_tag(Tag::ModelPixelScaleTag, &[0.5f64, 0.5, 0.0][..])
+            .unwrap();
+        tags.write_tag(
+            Tag::ModelTiepointTag,
+            &[0.0f64, 0.0, 0.0, 144.0, -37.0, 0.0][..],
+        )
+        .unwrap();
+        tags.write_tag(Tag::GeoKeyDirectoryTag, &[1u16, 1, 0, 0][..])
+            .unwrap();
+        tags.write_tag(Tag::GdalNodata, "-32768").unwrap();
+        image.write_data(&[10.0f32, -32768.0, 30.0, 40.0]).unwrap();
+
+        let (raster, projection) = Raster::read_geotiff(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            raster.origin,
+            Coord {
+                x: 144.25,
+                y: -37.25
+            }
+        );
+        assert_eq!(raster.pixel_size, Coord { x: 0.5, y: -0.5 });
+        assert_eq!(projection, Projection::Geographic);
+        assert_eq!(raster.values[0], 10.0);
+        assert!(raster.values[1].is_nan());
+        assert_eq!(raster.values[2..], [30.0, 40.0]);
+    }
+
+    #[test]
+    fn reads_integer_pixels_through_a_transformation() {
+        let path = std::env::temp_dir().join("elevated-cycling-transformation.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder.new_image::<GrayI16>(2, 2).unwrap();
+
+        let tags = image.encoder();
+        #[rustfmt::skip]
+        let transformation = [
+            0.5f64, 0.0, 0.0, 144.0,
+            0.0, -0.5, 0.0, -37.0,
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ];
+        tags.write_tag(Tag::ModelTransformationTag, &transformation[..])
+            .unwrap();
+        // pixel is point, so the transformation places the centre of the first pixel
+        tags.write_tag(Tag::GeoKeyDirectoryTag, &[1u16, 1, 0, 1, 1025, 0, 1, 2][..])
+            .unwrap();
+        tags.write_tag(Tag::GdalNodata, "-32768").unwrap();
+        image.write_data(&[10i16, -32768, 30, 40]).unwrap();
+
+        let (raster, _) = Raster::read_geotiff(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(raster.origin, Coord { x: 144.0, y: -37.0 });
+        assert_eq!(raster.pixel_size, Coord { x: 0.5, y: -0.5 });
+        assert_eq!(raster.values[0], 10.0);
+        assert!(raster.values[1].is_nan());
+        assert_eq!(raster.values[2..], [30.0, 40.0]);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::elevation::Raster;
+use anyhow::{anyhow, Context, Result};
+use geo::Coord;
+use std::{collections::HashMap, fs, path::Path};
+
+/// Reads an Esri ASCII grid.
+/// https://desktop.arcgis.com/en/arcmap/latest/manage-data/raster-and-images/esri-ascii-raster-format.htm
+pub fn read_asc(path: &Path) -> Result<Raster> {
+    parse_asc(&fs::read_to_string(path)?)
+        .with_context(|| format!("Expected a valid ASCII grid at {:?}", path))
+}
+
+/// A header of `key value` lines followed by rows of values from north to south.
+///
+/// The lower left is either the corner or the centre of the lower left cell,
+/// and cells are square unless GDAL's `dx` and `dy` are given instead of `cellsize`.
+fn parse_asc(text: &str) -> Result<Raster> {
+    let mut tokens = text.split_ascii_whitespace().peekable();
+    let mut header = HashMap::new();
+
+    while let Some(key) = tokens.next_if(|token| token.starts_with(|c: char| c.is_alphabetic())) {
+        let value: f64 = tokens
+            .next()
+            .ok_or_else(|| anyhow!("Expected a value for {:?}", key))?
+            .parse()?;
+
+        header.insert(key.to_ascii_lowercase(), value);
+    }
+
+    let field = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| anyhow!("Expected {:?} in the header", key))
+    };
+
+    let width = field("ncols")? as usize;
+    let height = field("nrows")? as usize;
+
+    let (dx, dy) = match header.get("cellsize") {
+        Some(cellsize) => (*cellsize, *cellsize),
+        None => (field("dx")?, field("dy")?),
+    };
+
+    let lower_left = match (header.get("xllcenter"), header.get("yllcenter")) {
+        (Some(x), Some(y)) => Coord { x: *x, y: *y },
+        _ => Coord {
+            x: field("xllcorner")? + dx / 2.0,
+            y: field("yllcorner")? + dy / 2.0,
+        },
+    };
+
+    let no_data = header.get("nodata_value").copied();
+
+    let values: Vec<f32> = tokens
+        .map(|token| -> Result<f32> {
+            let value: f64 = token.parse()?;
+
+            Ok(match no_data {
+                Some(no_data) if value == no_data => f32::NAN,
+                _ => value as f32,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    if values.len() != width * height {
+        return Err(anyhow!(
+            "Expected {} values for {} columns and {} rows, found {}",
+            width * height,
+            width,
+            height,
+            values.len()
+        ));
+    }
+
+    Ok(Raster {
+        width,
+        height,
+        origin: Coord {
+            x: lower_left.x,
+            y: lower_left.y + (height as f64 - 1.0) * dy,
+        },
+        pixel_size: Coord { x: dx, y: -dy },
+        values,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::elevation::{asc::parse_asc, ElevationSource, Interpolation, Missing};
+    use geo::Coord;
+
+    #[test]
+    fn reads_grids() {
+        let raster = parse_asc(
+            "ncols 3\n\
+             nrows 2\n\
+             xllcorner 144.0\n\
+             yllcorner -38.0\n\
+             cellsize 0.5\n\
+             NODATA_value -9999\n\
+             1 2 3\n\
+             4 -9999 6.5\n",
+        )
+        .unwrap();
+
+        assert_eq!(raster.extent().min(), Coord { x: 144.0, y: -38.0 });
+        assert_eq!(raster.extent().max(), Coord { x: 145.5, y: -37.0 });
+
+        let sample = |x, y| raster.sample(&Coord { x, y }, Interpolation::Nearest);
+        assert_eq!(sample(144.1, -37.1), Ok(1.0));
+        assert_eq!(sample(145.4, -37.9), Ok(6.5));
+        assert_eq!(sample(144.7, -37.7), Err(Missing::NoData));
+    }
+
+    #[test]
+    fn reads_centred_grids() {
+        let raster =
+            parse_asc("ncols 2\nnrows 2\nxllcenter 10\nyllcenter 20\ndx 2\ndy 1\n1 2\n3 4\n")
+                .unwrap();
+
+        assert_eq!(raster.origin, Coord { x: 10.0, y: 21.0 });
+        assert_eq!(raster.pixel_size, Coord { x: 2.0, y: -1.0 });
+    }
+
+    #[test]
+    fn counts_values() {
+        let text = "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3\n";
+
+        assert!(parse_asc(text).is_err());
+    }
+}
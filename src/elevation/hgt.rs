@@ -0,0 +1,139 @@
+use crate::elevation::Raster;
+use anyhow::{anyhow, Result};
+use geo::Coord;
+use std::{fs, path::Path};
+
+/// Marks samples without data, such as voids over water or in deep shadow.
+const NO_DATA: i16 = -32768;
+
+/// Reads an SRTM `.hgt` tile, which is named after its south west corner like `S38E144.hgt`.
+/// https://www.usgs.gov/centers/eros/science/usgs-eros-archive-digital-elevation-shuttle-radar-topography-mission-srtm-1
+pub fn read_hgt(path: &Path) -> Result<Raster> {
+    let name = path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Expected a tile name in {:?}", path))?;
+
+    parse_hgt(name, &fs::read(path)?)
+}
+
+/// Samples are big endian 16 bit metres, in rows from north to south
+/// with the outer rows and columns on the edges of the tile.
+fn parse_hgt(name: &str, bytes: &[u8]) -> Result<Raster> {
+    let corner = parse_corner(name)?;
+
+    let samples = bytes.len() / 2;
+    let size = samples.isqrt();
+
+    if !bytes.len().is_multiple_of(2) || size * size != samples || size < 2 {
+        return Err(anyhow!(
+            "Expected a square tile of 16 bit samples, found {} bytes",
+            bytes.len()
+        ));
+    }
+
+    let values = bytes
+        .chunks_exact(2)
+        .map(|sample| match i16::from_be_bytes([sample[0], sample[1]]) {
+            NO_DATA => f32::NAN,
+            elevation => elevation as f32,
+        })
+        .collect();
+
+    let spacing = 1.0 / (size - 1) as f64;
+
+    Ok(Raster {
+        width: size,
+        height: size,
+        origin: Coord {
+            x: corner.x,
+            y: corner.y + 1.0,
+        },
+        pixel_size: Coord {
+            x: spacing,
+            y: -spacing,
+        },
+        values,
+    })
+}
+
+/// The longitude and latitude of the south west corner, like `N37W122`.
+fn parse_corner(name: &str) -> Result<Coord> {
+    let expected = || anyhow!("Expected a tile name like N37W122, found {:?}", name);
+
+    let name = name.to_ascii_uppercase();
+    let (latitude, longitude) = name
+        .find(['E', 'W'])
+        .map(|split| name.split_at(split))
+        .ok_or_else(expected)?;
+
+    let degrees = |value: &str, positive: char, negative: char| -> Result<f64> {
+        let (sign, digits) = value.split_at(1);
+        let degrees: f64 = digits.parse().map_err(|_| expected())?;
+
+        match sign.chars().next() {
+            Some(sign) if sign == positive => Ok(degrees),
+            Some(sign) if sign == negative => Ok(-degrees),
+            _ => Err(expected()),
+        }
+    };
+
+    Ok(Coord {
+        x: degrees(longitude, 'E', 'W')?,
+        y: degrees(latitude, 'N', 'S')?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::elevation::{
+        hgt::{parse_corner, parse_hgt},
+        ElevationSource, Interpolation, Missing,
+    };
+    use geo::Coord;
+
+    #[test]
+    fn reads_corners() {
+        assert_eq!(
+            parse_corner("S38E144").unwrap(),
+            Coord { x: 144.0, y: -38.0 }
+        );
+        assert_eq!(
+            parse_corner("n37w122").unwrap(),
+            Coord { x: -122.0, y: 37.0 }
+        );
+        assert!(parse_corner("E144S38").is_err());
+        assert!(parse_corner("tile").is_err());
+    }
+
+    #[test]
+    fn reads_tiles() {
+        // 3x3 samples half a degree apart, with a void in the middle of the southern row
+        let samples: [i16; 9] = [30, 20, 10, 40, 50, 60, 70, -32768, 90];
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+
+        let raster = parse_hgt("S38E144", &bytes).unwrap();
+
+        assert_eq!(
+            raster.extent().min(),
+            Coord {
+                x: 143.75,
+                y: -38.25
+            }
+        );
+        assert_eq!(
+            raster.extent().max(),
+            Coord {
+                x: 145.25,
+                y: -36.75
+            }
+        );
+
+        let sample = |x, y| raster.sample(&Coord { x, y }, Interpolation::Nearest);
+        assert_eq!(sample(144.0, -37.0), Ok(30.0));
+        assert_eq!(sample(145.0, -37.5), Ok(60.0));
+        assert_eq!(sample(144.5, -38.0), Err(Missing::NoData));
+
+        assert!(parse_hgt("S38E144", &bytes[..16]).is_err());
+    }
+}
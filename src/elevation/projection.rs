@@ -0,0 +1,524 @@
+use crate::elevation::{ElevationSource, Interpolation, Missing};
+use anyhow::{anyhow, Result};
+use geo::{Coord, Rect};
+use geotiff::GeoKeyDirectory;
+
+/// Model types from the `GTModelTypeGeoKey`.
+const MODEL_TYPE_PROJECTED: u16 = 1;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+
+/// Marks keys whose value is described by other keys rather than an EPSG code.
+const USER_DEFINED: u16 = 32767;
+const COORD_TRANS_TRANSVERSE_MERCATOR: u16 = 1;
+const LINEAR_UNIT_METRE: u16 = 9001;
+
+/// How many points along each edge of a raster are unprojected to find its extent,
+/// since straight edges in a projection curve in longitude and latitude.
+const EXTENT_POINTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipsoid {
+    pub semi_major_axis: f64,
+    pub inverse_flattening: f64,
+}
+
+impl Ellipsoid {
+    pub const WGS84: Self = Self {
+        semi_major_axis: 6_378_137.0,
+        inverse_flattening: 298.257_223_563,
+    };
+
+    pub const GRS80: Self = Self {
+        semi_major_axis: 6_378_137.0,
+        inverse_flattening: 298.257_222_101,
+    };
+}
+
+/// The coordinate reference system of a raster, converting from longitude and latitude
+/// in degrees to the raster's model coordinates.
+///
+/// Datums are assumed to be close enough to WGS84 that only the ellipsoid matters,
+/// which is within a couple of metres for GDA94, GDA2020, NAD83 and ETRS89.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Longitude and latitude in degrees, like EPSG:4326.
+    Geographic,
+    /// Spherical mercator in metres, EPSG:3857.
+    WebMercator,
+    /// Transverse mercator in metres, as used by UTM and MGA zones.
+    TransverseMercator {
+        ellipsoid: Ellipsoid,
+        /// Degrees.
+        origin_longitude: f64,
+        /// Degrees.
+        origin_latitude: f64,
+        scale: f64,
+        false_easting: f64,
+        false_northing: f64,
+    },
+}
+
+impl Projection {
+    /// A UTM zone, where the southern hemisphere has a false northing of 10,000 km.
+    pub fn utm(ellipsoid: Ellipsoid, zone: u16, south: bool) -> Self {
+        Projection::TransverseMercator {
+            ellipsoid,
+            origin_longitude: zone as f64 * 6.0 - 183.0,
+            origin_latitude: 0.0,
+            scale: 0.9996,
+            false_easting: 500_000.0,
+            false_northing: if south { 10_000_000.0 } else { 0.0 },
+        }
+    }
+
+    /// The projections for EPSG codes of commonly used projected systems.
+    pub fn from_epsg(code: u16) -> Result<Self> {
+        let utm = |ellipsoid, zone, south| Ok(Self::utm(ellipsoid, zone, south));
+
+        match code {
+            3857 => Ok(Projection::WebMercator),
+            // WGS84 / UTM
+            32601..=32660 => utm(Ellipsoid::WGS84, code - 32600, false),
+            32701..=32760 => utm(Ellipsoid::WGS84, code - 32700, true),
+            // GDA94 / MGA, GDA2020 / MGA
+            28348..=28358 => utm(Ellipsoid::GRS80, code - 28300, true),
+            7846..=7859 => utm(Ellipsoid::GRS80, code - 7800, true),
+            // NAD83 / UTM, ETRS89 / UTM
+            26901..=26923 => utm(Ellipsoid::GRS80, code - 26900, false),
+            25828..=25838 => utm(Ellipsoid::GRS80, code - 25800, false),
+            _ => Err(anyhow!(
+                "Expected a supported projected CRS like UTM, MGA or Web Mercator, found EPSG:{}",
+                code
+            )),
+        }
+    }
+
+    /// Reads the coordinate reference system from the GeoKeys of a GeoTIFF.
+    /// https://docs.ogc.org/is/19-008r4/19-008r4.html#_requirements_class_projectedcrsgeokey
+    pub fn from_geo_keys(keys: &GeoKeyDirectory) -> Result<Self> {
+        match keys.model_type {
+            None | Some(MODEL_TYPE_GEOGRAPHIC) => return Ok(Projection::Geographic),
+            Some(MODEL_TYPE_PROJECTED) => {}
+            Some(model_type) => {
+                return Err(anyhow!(
+                    "Expected a geographic or projected raster, found model type {}",
+                    model_type
+                ))
+            }
+        }
+
+        if keys
+            .proj_linear_units
+            .is_some_and(|units| units != LINEAR_UNIT_METRE)
+        {
+            return Err(anyhow!("Expected projected coordinates in metres"));
+        }
+
+        match keys.projected_type {
+            Some(USER_DEFINED) | None => {}
+            Some(code) => return Self::from_epsg(code),
+        }
+
+        let ellipsoid = match (keys.geog_semi_major_axis, keys.geog_inv_flattening) {
+            (Some(semi_major_axis), Some(inverse_flattening)) => Ellipsoid {
+                semi_major_axis,
+                inverse_flattening,
+            },
+            _ => Ellipsoid::GRS80,
+        };
+
+        // projection codes for UTM zones, used when only the datum is user defined
+        match keys.projection {
+            Some(code @ 16001..=16060) => return Ok(Self::utm(ellipsoid, code - 16000, false)),
+            Some(code @ 16101..=16160) => return Ok(Self::utm(ellipsoid, code - 16100, true)),
+            _ => {}
+        }
+
+        match keys.proj_coord_trans {
+            Some(COORD_TRANS_TRANSVERSE_MERCATOR) => Ok(Projection::TransverseMercator {
+                ellipsoid,
+                origin_longitude: keys.proj_nat_origin_long.unwrap_or(0.0),
+                origin_latitude: keys.proj_nat_origin_lat.unwrap_or(0.0),
+                scale: keys.proj_scale_at_nat_origin.unwrap_or(1.0),
+                false_easting: keys.proj_false_easting.unwrap_or(0.0),
+                false_northing: keys.proj_false_northing.unwrap_or(0.0),
+            }),
+            _ => Err(anyhow!(
+                "Expected a projected CRS given by an EPSG code or a transverse mercator"
+            )),
+        }
+    }
+
+    /// Converts longitude and latitude in degrees to model coordinates.
+    pub fn project(&self, coord: &Coord) -> Coord {
+        match self {
+            Projection::Geographic => *coord,
+            Projection::WebMercator => {
+                let radius = Ellipsoid::WGS84.semi_major_axis;
+
+                Coord {
+                    x: radius * coord.x.to_radians(),
+                    y: radius
+                        * (std::f64::consts::FRAC_PI_4 + coord.y.to_radians() / 2.0)
+                            .tan()
+                            .ln(),
+                }
+            }
+            Projection::TransverseMercator {
+                ellipsoid,
+                origin_longitude,
+                origin_latitude,
+                scale,
+                false_easting,
+                false_northing,
+            } => {
+                let series = KruegerSeries::new(ellipsoid);
+                let (xi, eta) = series.forward(coord.y, coord.x - origin_longitude);
+
+                // northings are measured from the origin latitude along the central meridian
+                let origin_northing = series.a * series.forward(*origin_latitude, 0.0).0;
+
+                Coord {
+                    x: false_easting + scale * series.a * eta,
+                    y: false_northing + scale * (series.a * xi - origin_northing),
+                }
+            }
+        }
+    }
+
+    /// Converts model coordinates to longitude and latitude in degrees.
+    pub fn unproject(&self, coord: &Coord) -> Coord {
+        match self {
+            Projection::Geographic => *coord,
+            Projection::WebMercator => {
+                let radius = Ellipsoid::WGS84.semi_major_axis;
+
+                Coord {
+                    x: (coord.x / radius).to_degrees(),
+                    y: (2.0 * (coord.y / radius).exp().atan() - std::f64::consts::FRAC_PI_2)
+                        .to_degrees(),
+                }
+            }
+            Projection::TransverseMercator {
+                ellipsoid,
+                origin_longitude,
+                origin_latitude,
+                scale,
+                false_easting,
+                false_northing,
+            } => {
+                let series = KruegerSeries::new(ellipsoid);
+                let origin_northing = series.a * series.forward(*origin_latitude, 0.0).0;
+
+                let xi = ((coord.y - false_northing) / scale + origin_northing) / series.a;
+                let eta = (coord.x - false_easting) / scale / series.a;
+                let (latitude, longitude) = series.inverse(xi, eta);
+
+                Coord {
+                    x: origin_longitude + longitude,
+                    y: latitude,
+                }
+            }
+        }
+    }
+}
+
+/// Krüger's series for the transverse mercator to fourth order in the third flattening,
+/// accurate to well under a millimetre within a few thousand kilometres of the central meridian.
+/// https://en.wikipedia.org/wiki/Universal_Transverse_Mercator_coordinate_system#Simplified_formulae
+struct KruegerSeries {
+    /// Radius of the rectifying sphere.
+    a: f64,
+    n: f64,
+    alpha: [f64; 4],
+    beta: [f64; 4],
+    delta: [f64; 4],
+}
+
+impl KruegerSeries {
+    fn new(ellipsoid: &Ellipsoid) -> Self {
+        let f = 1.0 / ellipsoid.inverse_flattening;
+        let n = f / (2.0 - f);
+        let (n2, n3, n4) = (n * n, n * n * n, n * n * n * n);
+
+        Self {
+            a: ellipsoid.semi_major_axis / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0),
+            n,
+            alpha: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0 + 41.0 * n4 / 180.0,
+                13.0 * n2 / 48.0 - 3.0 * n3 / 5.0 + 557.0 * n4 / 1440.0,
+                61.0 * n3 / 240.0 - 103.0 * n4 / 140.0,
+                49561.0 * n4 / 161280.0,
+            ],
+            beta: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0 - n4 / 360.0,
+                n2 / 48.0 + n3 / 15.0 - 437.0 * n4 / 1440.0,
+                17.0 * n3 / 480.0 - 37.0 * n4 / 840.0,
+                4397.0 * n4 / 161280.0,
+            ],
+            delta: [
+                2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3 + 116.0 * n4 / 45.0,
+                7.0 * n2 / 3.0 - 8.0 * n3 / 5.0 - 227.0 * n4 / 45.0,
+                56.0 * n3 / 15.0 - 136.0 * n4 / 35.0,
+                4279.0 * n4 / 630.0,
+            ],
+        }
+    }
+
+    /// Latitude and longitude from the central meridian in degrees, to northing and easting
+    /// on a unit rectifying sphere.
+    fn forward(&self, latitude: f64, longitude: f64) -> (f64, f64) {
+        let (latitude, longitude) = (latitude.to_radians(), longitude.to_radians());
+        let e = 2.0 * self.n.sqrt() / (1.0 + self.n);
+
+        let t = (latitude.sin().atanh() - e * (e * latitude.sin()).atanh()).sinh();
+        let xi_prime = (t / longitude.cos()).atan();
+        let eta_prime = (longitude.sin() / (1.0 + t * t).sqrt()).atanh();
+
+        let mut xi = xi_prime;
+        let mut eta = eta_prime;
+
+        for (j, alpha) in self.alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi += alpha * (k * xi_prime).sin() * (k * eta_prime).cosh();
+            eta += alpha * (k * xi_prime).cos() * (k * eta_prime).sinh();
+        }
+
+        (xi, eta)
+    }
+
+    /// Northing and easting on a unit rectifying sphere, to latitude and longitude
+    /// from the central meridian in degrees.
+    fn inverse(&self, xi: f64, eta: f64) -> (f64, f64) {
+        let mut xi_prime = xi;
+        let mut eta_prime = eta;
+
+        for (j, beta) in self.beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+
+        let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let mut latitude = chi;
+
+        for (j, delta) in self.delta.iter().enumerate() {
+            latitude += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+
+        let longitude = (eta_prime.sinh() / xi_prime.cos()).atan();
+
+        (latitude.to_degrees(), longitude.to_degrees())
+    }
+}
+
+/// An elevation source in a projected coordinate system, sampled by longitude and latitude.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reprojected<S> {
+    pub source: S,
+    pub projection: Projection,
+}
+
+impl<S: ElevationSource> ElevationSource for Reprojected<S> {
+    fn extent(&self) -> Rect {
+        let extent = self.source.extent();
+        let (min, max) = (extent.min(), extent.max());
+
+        let along = |from: Coord, to: Coord| {
+            (0..=EXTENT_POINTS)
+                .map(move |step| from + (to - from) * (step as f64 / EXTENT_POINTS as f64))
+        };
+
+        let corners = [
+            min,
+            Coord { x: max.x, y: min.y },
+            max,
+            Coord { x: min.x, y: max.y },
+        ];
+
+        let unprojected = (0..4)
+            .flat_map(|corner| along(corners[corner], corners[(corner + 1) % 4]))
+            .map(|coord| self.projection.unproject(&coord))
+            .collect::<Vec<_>>();
+
+        let first = unprojected[0];
+        let (min, max) = unprojected
+            .iter()
+            .fold((first, first), |(min, max), coord| {
+                (
+                    Coord {
+                        x: min.x.min(coord.x),
+                        y: min.y.min(coord.y),
+                    },
+                    Coord {
+                        x: max.x.max(coord.x),
+                        y: max.y.max(coord.y),
+                    },
+                )
+            });
+
+        Rect::new(min, max)
+    }
+
+    fn sample(&self, coord: &Coord, interpolation: Interpolation) -> Result<f64, Missing> {
+        self.source
+            .sample(&self.projection.project(coord), interpolation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::elevation::{
+        projection::{Ellipsoid, Projection, Reprojected},
+        ElevationSource, Interpolation, Raster,
+    };
+    use geo::Coord;
+    use geotiff::GeoKeyDirectory;
+
+    /// Flinders Peak, the worked example for MGA zone 55 from the GDA technical manual.
+    const FLINDERS_PEAK: Coord = Coord {
+        x: 144.0 + 25.0 / 60.0 + 29.5244 / 3600.0,
+        y: -(37.0 + 57.0 / 60.0 + 3.7203 / 3600.0),
+    };
+
+    fn assert_near(actual: Coord, expected: Coord, tolerance: f64) {
+        assert!(
+            (actual.x - expected.x).abs() < tolerance && (actual.y - expected.y).abs() < tolerance,
+            "expected {expected:?}, found {actual:?}"
+        );
+    }
+
+    #[test]
+    fn projects_to_mga() {
+        let mga55 = Projection::from_epsg(28355).unwrap();
+        let projected = mga55.project(&FLINDERS_PEAK);
+
+        assert_near(
+            projected,
+            Coord {
+                x: 273_741.297,
+                y: 5_796_489.777,
+            },
+            0.005,
+        );
+        assert_near(mga55.unproject(&projected), FLINDERS_PEAK, 1e-9);
+    }
+
+    #[test]
+    fn offsets_the_origin_latitude() {
+        let projection = Projection::TransverseMercator {
+            ellipsoid: Ellipsoid::WGS84,
+            origin_longitude: -2.0,
+            origin_latitude: 49.0,
+            scale: 0.9996012717,
+            false_easting: 400_000.0,
+            false_northing: -100_000.0,
+        };
+        let origin = Coord { x: -2.0, y: 49.0 };
+
+        assert_near(
+            projection.project(&origin),
+            Coord {
+                x: 400_000.0,
+                y: -100_000.0,
+            },
+            1e-6,
+        );
+
+        let london = Coord {
+            x: -0.1276,
+            y: 51.5072,
+        };
+        assert_near(
+            projection.unproject(&projection.project(&london)),
+            london,
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn projects_to_web_mercator() {
+        let projection = Projection::from_epsg(3857).unwrap();
+        let corner = Coord {
+            x: 180.0,
+            y: 85.051_128_779_806_6,
+        };
+
+        assert_near(
+            projection.project(&corner),
+            Coord {
+                x: 20_037_508.342_789,
+                y: 20_037_508.342_789,
+            },
+            0.001,
+        );
+        assert_near(
+            projection.unproject(&projection.project(&corner)),
+            corner,
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn reads_geo_keys() {
+        assert_eq!(
+            Projection::from_geo_keys(&GeoKeyDirectory::default()).unwrap(),
+            Projection::Geographic
+        );
+
+        let keys = |projected_type| GeoKeyDirectory {
+            model_type: Some(1),
+            projected_type: Some(projected_type),
+            ..GeoKeyDirectory::default()
+        };
+
+        assert_eq!(
+            Projection::from_geo_keys(&keys(32755)).unwrap(),
+            Projection::utm(Ellipsoid::WGS84, 55, true)
+        );
+        assert!(Projection::from_geo_keys(&keys(2193)).is_err());
+
+        let user_defined = GeoKeyDirectory {
+            projection: Some(16033),
+            ..keys(32767)
+        };
+        assert_eq!(
+            Projection::from_geo_keys(&user_defined).unwrap(),
+            Projection::utm(Ellipsoid::GRS80, 33, false)
+        );
+    }
+
+    #[test]
+    fn samples_projected_rasters_by_longitude_and_latitude() {
+        let mga55 = Projection::from_epsg(28355).unwrap();
+
+        // 1 km of 10 m pixels around Flinders Peak, rising 1 m for every 100 m east
+        let origin = Coord {
+            x: 273_005.0,
+            y: 5_796_995.0,
+        };
+        let raster = Raster {
+            width: 100,
+            height: 100,
+            origin,
+            pixel_size: Coord { x: 10.0, y: -10.0 },
+            values: (0..100 * 100)
+                .map(|index| ((origin.x + (index % 100) as f64 * 10.0) / 100.0) as f32)
+                .collect(),
+        };
+        let reprojected = Reprojected {
+            source: raster,
+            projection: mga55,
+        };
+
+        let elevation = reprojected
+            .sample(&FLINDERS_PEAK, Interpolation::Bilinear)
+            .unwrap();
+        assert!((elevation - 2_737.412_97).abs() < 0.001);
+
+        let extent = reprojected.extent();
+        assert!(extent.min().x < FLINDERS_PEAK.x && FLINDERS_PEAK.x < extent.max().x);
+        assert!(extent.min().y < FLINDERS_PEAK.y && FLINDERS_PEAK.y < extent.max().y);
+        assert!(extent.width() < 0.02 && extent.height() < 0.01);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{circuit::Circuit, osm::WayTags};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde_json::{json, Value};
+use std::io::Write;
+
+/// Writes the circuit as a GeoJSON `FeatureCollection`.
+///
+/// The first feature is the whole route, followed by one feature per edge
+/// ridden so the route can be coloured by steepness or road.
+pub fn write_geojson(mut writer: impl Write, circuit: &Circuit, name: &str) -> Result<()> {
+    serde_json::to_writer(&mut writer, &to_geojson(circuit, name)?)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn to_geojson(circuit: &Circuit, name: &str) -> Result<Value> {
+    let position = |node_id: i64| -> Result<_> {
+        circuit
+            .nodes
+            .get(&node_id)
+            .ok_or_else(|| anyhow!("Expected to find node {} in the circuit", node_id))
+    };
+
+    let coordinates: Vec<[f64; 2]> = circuit
+        .node_ids()
+        .map(|node_id| -> Result<_> {
+            let (coord, _) = position(node_id)?;
+            Ok([coord.x, coord.y])
+        })
+        .try_collect()?;
+
+    // a LineString needs two positions, which a circuit that never leaves the origin lacks
+    if coordinates.len() < 2 {
+        return Err(anyhow!(
+            "Expected the circuit to visit at least 2 nodes, found {}",
+            coordinates.len()
+        ));
+    }
+
+    let route = json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": coordinates,
+        },
+        "properties": {
+            "name": name,
+        },
+    });
+
+    let legs = [("ascent", &circuit.ascent), ("descent", &circuit.descent)];
+
+    let segments: Vec<Value> = legs
+        .into_iter()
+        .flat_map(|(leg, node_ids)| node_ids.iter().tuple_windows().map(move |ids| (leg, ids)))
+        .map(|(leg, (source_node_id, target_node_id))| -> Result<_> {
+            let (source, elevation_start) = position(*source_node_id)?;
+            let (target, elevation_end) = position(*target_node_id)?;
+
+            let edge = circuit
+                .gradients
+                .edge_weight(*source_node_id, *target_node_id)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Expected to find an edge from {} to {}",
+                        source_node_id,
+                        target_node_id
+                    )
+                })?;
+
+            let segment = edge.segment;
+            let way = edge.way_id.and_then(|way_id| circuit.ways.get(&way_id));
+            let tag = |tag: fn(&WayTags) -> &Option<String>| way.and_then(|way| tag(way).clone());
+
+            Ok(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [[source.x, source.y], [target.x, target.y]],
+                },
+                "properties": {
+                    "leg": leg,
+                    "gradient": segment.gradient,
+                    "distance_m": segment.distance,
+                    "elevation_start": elevation_start,
+                    "elevation_end": elevation_end,
+                    "way_id": edge.way_id,
+                    "name": tag(|way| &way.name),
+                    "highway": tag(|way| &way.highway),
+                    "surface": tag(|way| &way.surface),
+                },
+            }))
+        })
+        .try_collect()?;
+
+    let features = [route].into_iter().chain(segments).collect_vec();
+
+    Ok(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        circuit::{Circuit, CircuitEdge},
+        geojson::to_geojson,
+        osm::WayTags,
+        segment::Segment,
+    };
+    use geo::Coord;
+    use indexmap::IndexMap;
+    use petgraph::prelude::DiGraphMap;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn circuits_need_to_leave_the_origin() {
+        let circuit = Circuit {
+            nodes: IndexMap::from([(1, (Coord { x: 0.0, y: 0.0 }, 10.0))]),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1],
+            descent: vec![1],
+        };
+
+        assert!(to_geojson(&circuit, "Circuit").is_err());
+    }
+
+    #[test]
+    fn features_per_segment() {
+        let segment = Segment {
+            distance: 100.0,
+            gradient: 0.1,
+            climbing: 10.0,
+            descending: 0.0,
+            max_gradient: 0.1,
+            min_gradient: 0.1,
+        };
+
+        let circuit = Circuit {
+            nodes: IndexMap::from([
+                (2, (Coord { x: 1.0, y: 1.0 }, 20.0)),
+                (1, (Coord { x: 0.0, y: 0.0 }, 10.0)),
+            ]),
+            gradients: DiGraphMap::from_edges([
+                (
+                    1,
+                    2,
+                    CircuitEdge {
+                        segment,
+                        way_id: Some(7),
+                    },
+                ),
+                (
+                    2,
+                    1,
+                    CircuitEdge {
+                        segment: segment.reversed(),
+                        way_id: None,
+                    },
+                ),
+            ]),
+            ways: HashMap::from([(
+                7,
+                WayTags {
+                    highway: Some("tertiary".to_string()),
+                    name: Some("Summit Road".to_string()),
+                    ..WayTags::default()
+                },
+            )]),
+            ascent: vec![1, 2],
+            descent: vec![2, 1],
+        };
+
+        let geojson = to_geojson(&circuit, "Circuit").unwrap();
+
+        assert_eq!(
+            geojson,
+            json!({
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "geometry": {
+                            "type": "LineString",
+                            "coordinates": [[0.0, 0.0], [1.0, 1.0], [0.0, 0.0]],
+                        },
+                        "properties": { "name": "Circuit" },
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": {
+                            "type": "LineString",
+                            "coordinates": [[0.0, 0.0], [1.0, 1.0]],
+                        },
+                        "properties": {
+                            "leg": "ascent",
+                            "gradient": 0.1,
+                            "distance_m": 100.0,
+                            "elevation_start": 10.0,
+                            "elevation_end": 20.0,
+                            "way_id": 7,
+                            "name": "Summit Road",
+                            "highway": "tertiary",
+                            "surface": null,
+                        },
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": {
+                            "type": "LineString",
+                            "coordinates": [[1.0, 1.0], [0.0, 0.0]],
+                        },
+                        "properties": {
+                            "leg": "descent",
+                            "gradient": -0.1,
+                            "distance_m": 100.0,
+                            "elevation_start": 20.0,
+                            "elevation_end": 10.0,
+                            "way_id": null,
+                            "name": null,
+                            "highway": null,
+                            "surface": null,
+                        },
+                    },
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use crate::circuit::Circuit;
+use anyhow::{anyhow, Result};
+use std::io::Write;
+
+/// Writes the circuit as a GPX 1.1 track, with an elevation for every point.
+pub fn write_gpx(mut writer: impl Write, circuit: &Circuit, name: &str) -> Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="elevated-cycling" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    writeln!(writer, "  <trk>")?;
+    writeln!(writer, "    <name>{}</name>", escape(name))?;
+    writeln!(writer, "    <trkseg>")?;
+
+    for node_id in circuit.node_ids() {
+        let (coord, elevation) = circuit
+            .nodes
+            .get(&node_id)
+            .ok_or_else(|| anyhow!("Expected to find node {} in the circuit", node_id))?;
+
+        writeln!(
+            writer,
+            r#"      <trkpt lat="{}" lon="{}"><ele>{}</ele></trkpt>"#,
+            coord.y, coord.x, elevation
+        )?;
+    }
+
+    writeln!(writer, "    </trkseg>")?;
+    writeln!(writer, "  </trk>")?;
+    writeln!(writer, "</gpx>")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{circuit::Circuit, gpx::write_gpx};
+    use geo::Coord;
+    use indexmap::IndexMap;
+    use petgraph::prelude::DiGraphMap;
+    use std::collections::HashMap;
+
+    #[test]
+    fn writes_track_with_elevations() {
+        let circuit = Circuit {
+            nodes: IndexMap::from([
+                (2, (Coord { x: 144.9, y: -37.8 }, 120.5)),
+                (1, (Coord { x: 144.8, y: -37.7 }, 10.0)),
+            ]),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1, 2],
+            descent: vec![2, 1],
+        };
+
+        let mut gpx = Vec::new();
+        write_gpx(&mut gpx, &circuit, "Hills & valleys").unwrap();
+
+        assert_eq!(
+            String::from_utf8(gpx).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="elevated-cycling" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>Hills &amp; valleys</name>
+    <trkseg>
+      <trkpt lat="-37.7" lon="144.8"><ele>10</ele></trkpt>
+      <trkpt lat="-37.8" lon="144.9"><ele>120.5</ele></trkpt>
+      <trkpt lat="-37.7" lon="144.8"><ele>10</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>
+"#
+        );
+    }
+
+    #[test]
+    fn missing_node_is_an_error() {
+        let circuit = Circuit {
+            nodes: IndexMap::new(),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1],
+            descent: vec![1],
+        };
+
+        assert!(write_gpx(Vec::new(), &circuit, "Circuit").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+/// A step of bootstrapping that applies a source file to the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Ingest,
+    Ways,
+    Coordinates,
+    Elevations,
+    Changes,
+    Profiles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A run of a stage against a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestJob {
+    pub id: i64,
+    pub stage: Stage,
+    pub source_path: String,
+    pub source_hash: String,
+    pub status: JobStatus,
+}
+
+/// A source file, recognised by the hash of its contents rather than its path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub path: String,
+    pub hash: String,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Ingest => "ingest",
+            Stage::Ways => "ways",
+            Stage::Coordinates => "coordinates",
+            Stage::Elevations => "elevations",
+            Stage::Changes => "changes",
+            Stage::Profiles => "profiles",
+        }
+    }
+
+    /// Stages that add nodes which later stages have to fill in again.
+    fn adds_nodes(&self) -> bool {
+        matches!(self, Stage::Ingest | Stage::Ways | Stage::Changes)
+    }
+
+    /// Returns true when completing this stage leaves work for `stage` to do again,
+    /// like new nodes without coordinates or coordinates without elevations.
+    fn invalidates(&self, stage: Stage) -> bool {
+        match stage {
+            Stage::Ingest | Stage::Ways | Stage::Changes => false,
+            Stage::Coordinates => self.adds_nodes(),
+            Stage::Elevations | Stage::Profiles => self.adds_nodes() || *self == Stage::Coordinates,
+        }
+    }
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = anyhow::Error;
+
+    fn from_str(stage: &str) -> Result<Self> {
+        [
+            Stage::Ingest,
+            Stage::Ways,
+            Stage::Coordinates,
+            Stage::Elevations,
+            Stage::Changes,
+            Stage::Profiles,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == stage)
+        .ok_or_else(|| anyhow!("Expected a known stage, found {:?}", stage))
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self> {
+        [JobStatus::Running, JobStatus::Completed, JobStatus::Failed]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == status)
+            .ok_or_else(|| anyhow!("Expected a known job status, found {:?}", status))
+    }
+}
+
+impl Display for IngestJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>4}  {:<11}  {:<9}  {}  {}",
+            self.id,
+            self.stage.as_str(),
+            self.status.as_str(),
+            &self.source_hash[..self.source_hash.len().min(12)],
+            self.source_path
+        )
+    }
+}
+
+impl Source {
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+
+        let hash = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(Self {
+            path: path.to_string_lossy().into_owned(),
+            hash,
+        })
+    }
+}
+
+/// Returns true when a stage has completed for a source since the last time
+/// nodes were added or moved, so running it again would change nothing.
+pub fn is_applied(stage: Stage, source: &Source, jobs: &[IngestJob]) -> bool {
+    let completed = |job: &&IngestJob| job.status == JobStatus::Completed;
+
+    let invalidated = jobs
+        .iter()
+        .filter(completed)
+        .filter(|job| job.stage.invalidates(stage))
+        .map(|job| job.id)
+        .max();
+
+    jobs.iter()
+        .filter(completed)
+        .filter(|job| job.stage == stage && job.source_hash == source.hash)
+        .any(|job| invalidated.is_none_or(|invalidated| job.id > invalidated))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::jobs::{is_applied, IngestJob, JobStatus, Source, Stage};
+
+    fn source(hash: &str) -> Source {
+        Source {
+            path: format!("{}.tif", hash),
+            hash: hash.to_string(),
+        }
+    }
+
+    fn job(id: i64, stage: Stage, hash: &str, status: JobStatus) -> IngestJob {
+        IngestJob {
+            id,
+            stage,
+            source_path: format!("{}.tif", hash),
+            source_hash: hash.to_string(),
+            status,
+        }
+    }
+
+    #[test]
+    fn completed_sources_are_applied() {
+        let jobs = [
+            job(1, Stage::Ingest, "map", JobStatus::Completed),
+            job(2, Stage::Elevations, "a", JobStatus::Completed),
+            job(3, Stage::Elevations, "b", JobStatus::Failed),
+            job(4, Stage::Elevations, "c", JobStatus::Running),
+        ];
+
+        assert!(is_applied(Stage::Ingest, &source("map"), &jobs));
+        assert!(is_applied(Stage::Elevations, &source("a"), &jobs));
+        assert!(!is_applied(Stage::Elevations, &source("b"), &jobs));
+        assert!(!is_applied(Stage::Elevations, &source("c"), &jobs));
+        assert!(!is_applied(Stage::Elevations, &source("d"), &jobs));
+        assert!(!is_applied(Stage::Coordinates, &source("a"), &jobs));
+    }
+
+    #[test]
+    fn adding_nodes_reapplies_later_stages() {
+        let jobs = [
+            job(1, Stage::Ingest, "map", JobStatus::Completed),
+            job(2, Stage::Elevations, "a", JobStatus::Completed),
+            job(3, Stage::Ways, "other", JobStatus::Completed),
+            job(4, Stage::Ways, "broken", JobStatus::Failed),
+        ];
+
+        assert!(!is_applied(Stage::Elevations, &source("a"), &jobs));
+        assert!(is_applied(Stage::Ingest, &source("map"), &jobs));
+    }
+
+    #[test]
+    fn coordinates_reapply_elevations() {
+        // elevations found nothing to do before the nodes had coordinates.
+        let mut jobs = vec![
+            job(1, Stage::Ways, "map", JobStatus::Completed),
+            job(2, Stage::Elevations, "a", JobStatus::Completed),
+            job(3, Stage::Coordinates, "map", JobStatus::Completed),
+        ];
+
+        assert!(!is_applied(Stage::Elevations, &source("a"), &jobs));
+        assert!(!is_applied(Stage::Profiles, &source("a"), &jobs));
+        assert!(is_applied(Stage::Coordinates, &source("map"), &jobs));
+
+        jobs.push(job(4, Stage::Elevations, "a", JobStatus::Completed));
+        assert!(is_applied(Stage::Elevations, &source("a"), &jobs));
+    }
+
+    #[test]
+    fn parses_stored_names() {
+        for stage in [
+            Stage::Ingest,
+            Stage::Ways,
+            Stage::Coordinates,
+            Stage::Elevations,
+            Stage::Changes,
+            Stage::Profiles,
+        ] {
+            assert_eq!(stage.as_str().parse::<Stage>().unwrap(), stage);
+        }
+
+        assert_eq!("failed".parse::<JobStatus>().unwrap(), JobStatus::Failed);
+        assert!("done".parse::<JobStatus>().is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::circuit::Circuit;
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use reqwest::Url;
+use std::{path::Path, process::Command};
+
+/// Encodes the ascent and descent as two lines in a MapBBCode `[map]` block,
+/// the format `public/index.html` reads from its `mapbbcode` query parameter.
+pub fn encode_mapbbcode(circuit: &Circuit) -> Result<String> {
+    let line = |node_ids: &[i64]| -> Result<String> {
+        // a single position would be read as a marker rather than a line
+        if node_ids.len() < 2 {
+            return Err(anyhow!(
+                "Expected each leg of the circuit to visit at least 2 nodes, found {}",
+                node_ids.len()
+            ));
+        }
+
+        node_ids
+            .iter()
+            .map(|node_id| -> Result<_> {
+                let (coord, _) = circuit
+                    .nodes
+                    .get(node_id)
+                    .ok_or_else(|| anyhow!("Expected to find node {} in the circuit", node_id))?;
+
+                // MapBBCode uses latitude first, five decimals is about a metre.
+                Ok(format!("{:.5},{:.5}", coord.y, coord.x))
+            })
+            .process_results(|mut coords| coords.join(" "))
+    };
+
+    Ok(format!(
+        "[map]{}(red|Ascent); {}(blue|Descent)[/map]",
+        line(&circuit.ascent)?,
+        line(&circuit.descent)?
+    ))
+}
+
+/// Adds the title and MapBBCode to the viewer's query string.
+///
+/// `viewer` is either a URL or a path to `public/index.html` on this machine.
+pub fn viewer_url(viewer: &str, title: &str, mapbbcode: &str) -> Result<Url> {
+    let mut url = match Url::parse(viewer) {
+        Ok(url) if matches!(url.scheme(), "http" | "https" | "file") => url,
+        _ => {
+            let path = Path::new(viewer).canonicalize()?;
+            Url::from_file_path(&path)
+                .map_err(|_| anyhow!("Expected {:?} to be a valid file path", path))?
+        }
+    };
+
+    url.query_pairs_mut()
+        .append_pair("title", title)
+        .append_pair("mapbbcode", mapbbcode);
+
+    Ok(url)
+}
+
+/// Opens the URL with the desktop's default browser.
+pub fn open_url(url: &Url) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let status = command.arg(url.as_str()).status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("Failed to open {} ({})", url, status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        circuit::Circuit,
+        mapbbcode::{encode_mapbbcode, viewer_url},
+    };
+    use geo::Coord;
+    use indexmap::IndexMap;
+    use petgraph::prelude::DiGraphMap;
+    use std::collections::HashMap;
+
+    #[test]
+    fn encodes_ascent_and_descent() {
+        let circuit = Circuit {
+            nodes: IndexMap::from([
+                (
+                    3,
+                    (
+                        Coord {
+                            x: 144.91,
+                            y: -37.81,
+                        },
+                        30.0,
+                    ),
+                ),
+                (2, (Coord { x: 144.9, y: -37.8 }, 20.0)),
+                (
+                    1,
+                    (
+                        Coord {
+                            x: 144.123456,
+                            y: -37.7,
+                        },
+                        10.0,
+                    ),
+                ),
+            ]),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1, 2, 3],
+            descent: vec![3, 1],
+        };
+
+        assert_eq!(
+            encode_mapbbcode(&circuit).unwrap(),
+            "[map]-37.70000,144.12346 -37.80000,144.90000 -37.81000,144.91000(red|Ascent); \
+             -37.81000,144.91000 -37.70000,144.12346(blue|Descent)[/map]"
+        );
+    }
+
+    #[test]
+    fn legs_need_to_leave_the_origin() {
+        let circuit = Circuit {
+            nodes: IndexMap::from([(1, (Coord { x: 144.9, y: -37.8 }, 10.0))]),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1],
+            descent: vec![1],
+        };
+
+        assert!(encode_mapbbcode(&circuit).is_err());
+    }
+
+    #[test]
+    fn viewer_url_escapes_query() {
+        let url = viewer_url(
+            "https://example.com/index.html",
+            "Circuit",
+            "[map]1,2 3,4(red|Ascent)[/map]",
+        )
+        .unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/index.html?title=Circuit&mapbbcode=%5Bmap%5D1%2C2+3%2C4%28red%7CAscent%29%5B%2Fmap%5D"
+        );
+    }
+
+    #[test]
+    fn viewer_url_from_path() {
+        let url = viewer_url("public/index.html", "Circuit", "[map][/map]").unwrap();
+
+        assert_eq!(url.scheme(), "file");
+        assert!(url.path().ends_with("/public/index.html"));
+    }
+}
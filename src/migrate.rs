@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool,
+};
+
+/// Versioned schema changes from `migrations/`, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies every migration the database has not seen yet.
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    let pending = query_pending_versions(pool).await?;
+
+    info!("Applying {} migrations", pending.len());
+
+    MIGRATOR.run(pool).await?;
+
+    info!(
+        "Schema is at version {}",
+        latest_version().unwrap_or_default()
+    );
+
+    Ok(())
+}
+
+/// Errors when the database is missing migrations embedded in this binary.
+pub async fn ensure_up_to_date(pool: &PgPool) -> Result<()> {
+    let pending = query_pending_versions(pool).await?;
+
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Expected the schema to be up to date but migrations {:?} are pending, run `migrate` first",
+            pending
+        ))
+    }
+}
+
+async fn query_pending_versions(pool: &PgPool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<Vec<_>>();
+
+    let available = MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .collect::<Vec<_>>();
+
+    Ok(pending_versions(&available, &applied))
+}
+
+fn latest_version() -> Option<i64> {
+    MIGRATOR.iter().map(|migration| migration.version).max()
+}
+
+/// Versions that are available but have not been applied, in order.
+fn pending_versions(available: &[i64], applied: &[i64]) -> Vec<i64> {
+    let mut pending = available
+        .iter()
+        .filter(|version| !applied.contains(version))
+        .copied()
+        .collect::<Vec<_>>();
+
+    pending.sort();
+    pending
+}
+
+#[cfg(test)]
+mod test {
+    use crate::migrate::{pending_versions, MIGRATOR};
+
+    #[test]
+    fn pending_versions_are_missing_from_applied() {
+        assert_eq!(pending_versions(&[3, 1, 2], &[1]), vec![2, 3]);
+        assert_eq!(pending_versions(&[1, 2], &[1, 2]), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn migrations_are_embedded() {
+        let versions = MIGRATOR
+            .iter()
+            .map(|migration| migration.version)
+            .collect::<Vec<_>>();
+
+        assert_eq!(versions.first(), Some(&1));
+    }
+}
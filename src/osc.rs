@@ -0,0 +1,278 @@
+use crate::{osm::CyclableWays, rules::WayRules};
+use anyhow::{anyhow, Context, Result};
+use flate2::bufread::MultiGzDecoder;
+use geo::Coord;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader, XmlVersion,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// The nodes and ways created, modified or deleted by an OSM change file.
+///
+/// Relations aren't read, so turn restrictions only change with a full bootstrap.
+#[derive(Debug, Default, PartialEq)]
+pub struct OsmChange {
+    /// Coordinates of nodes that were created or modified.
+    pub nodes: HashMap<i64, Coord>,
+    pub deleted_node_ids: Vec<i64>,
+    /// Ways that were created or modified.
+    pub ways: Vec<ChangedWay>,
+    pub deleted_way_ids: Vec<i64>,
+}
+
+/// The full contents of a way after a change.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChangedWay {
+    pub id: i64,
+    pub refs: Vec<i64>,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Upsert,
+    Delete,
+}
+
+impl OsmChange {
+    /// The cyclable ways amongst the created or modified ways.
+    pub fn cyclable_ways(&self, rules: &WayRules) -> CyclableWays {
+        let mut cyclable = CyclableWays::default();
+
+        for way in &self.ways {
+            let tags: Vec<(&str, &str)> = way
+                .tags
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+
+            cyclable.extend(CyclableWays::from_way(
+                way.id,
+                way.refs.iter().copied(),
+                &tags,
+                rules,
+            ));
+        }
+
+        cyclable
+    }
+
+    /// Every way whose edges have to be replaced or removed.
+    pub fn changed_way_ids(&self) -> Vec<i64> {
+        self.ways
+            .iter()
+            .map(|way| way.id)
+            .chain(self.deleted_way_ids.iter().copied())
+            .collect()
+    }
+}
+
+/// Reads an `.osc` file, which may be gzipped.
+/// https://wiki.openstreetmap.org/wiki/OsmChange
+pub fn read_osm_change(path: &Path) -> Result<OsmChange> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // gzip files start with these magic bytes
+    let gzipped = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+
+    let change = if gzipped {
+        parse_osm_change(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        parse_osm_change(reader)
+    };
+
+    change.with_context(|| format!("Expected a valid OSM change file at {:?}", path))
+}
+
+fn parse_osm_change(reader: impl BufRead) -> Result<OsmChange> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buffer = Vec::new();
+
+    let mut change = OsmChange::default();
+    let mut action = None;
+    let mut way: Option<ChangedWay> = None;
+
+    loop {
+        let event = reader.read_event_into(&mut buffer)?;
+
+        let (element, is_empty) = match &event {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(element) => {
+                match element.name().as_ref() {
+                    "create" | "modify" | "delete" => action = None,
+                    "way" => finish_way(&mut change, action, way.take()),
+                    _ => {}
+                }
+
+                buffer.clear();
+                continue;
+            }
+            Event::Eof => break,
+            _ => {
+                buffer.clear();
+                continue;
+            }
+        };
+
+        match element.name().as_ref() {
+            "create" | "modify" => action = Some(Action::Upsert),
+            "delete" => action = Some(Action::Delete),
+            "node" => {
+                let id = attribute(element, "id")?.parse()?;
+
+                match action {
+                    Some(Action::Upsert) => {
+                        let x = attribute(element, "lon")?.parse()?;
+                        let y = attribute(element, "lat")?.parse()?;
+                        change.nodes.insert(id, Coord { x, y });
+                    }
+                    Some(Action::Delete) => change.deleted_node_ids.push(id),
+                    None => {}
+                }
+            }
+            "way" => {
+                let changed = ChangedWay {
+                    id: attribute(element, "id")?.parse()?,
+                    ..ChangedWay::default()
+                };
+
+                if is_empty {
+                    finish_way(&mut change, action, Some(changed));
+                } else {
+                    way = Some(changed);
+                }
+            }
+            "nd" => {
+                if let Some(way) = way.as_mut() {
+                    way.refs.push(attribute(element, "ref")?.parse()?);
+                }
+            }
+            "tag" => {
+                if let Some(way) = way.as_mut() {
+                    way.tags
+                        .push((attribute(element, "k")?, attribute(element, "v")?));
+                }
+            }
+            _ => {}
+        }
+
+        buffer.clear();
+    }
+
+    Ok(change)
+}
+
+fn finish_way(change: &mut OsmChange, action: Option<Action>, way: Option<ChangedWay>) {
+    match (action, way) {
+        (Some(Action::Upsert), Some(way)) => change.ways.push(way),
+        (Some(Action::Delete), Some(way)) => change.deleted_way_ids.push(way.id),
+        _ => {}
+    }
+}
+
+fn attribute(element: &BytesStart<'_>, key: &str) -> Result<String> {
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+
+        if attribute.key.as_ref() == key {
+            return Ok(attribute
+                .normalized_value(XmlVersion::Implicit1_0)?
+                .into_owned());
+        }
+    }
+
+    Err(anyhow!(
+        "Expected to find attribute {:?} on {:?}",
+        key,
+        element.name().as_ref()
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        osc::{parse_osm_change, ChangedWay},
+        rules::WayRules,
+    };
+    use geo::Coord;
+    use std::collections::HashMap;
+
+    const CHANGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6" generator="test">
+  <create>
+    <node id="1" version="1" lat="-37.8" lon="144.9"/>
+    <way id="10" version="1">
+      <nd ref="1"/>
+      <nd ref="2"/>
+      <tag k="highway" v="residential"/>
+      <tag k="name" v="Smith &amp; Sons Lane"/>
+    </way>
+  </create>
+  <modify>
+    <node id="2" version="2" lat="-37.81" lon="144.91">
+      <tag k="barrier" v="gate"/>
+    </node>
+    <way id="11" version="3">
+      <nd ref="2"/>
+      <nd ref="3"/>
+      <tag k="highway" v="motorway"/>
+    </way>
+  </modify>
+  <delete>
+    <node id="4" version="5"/>
+    <way id="12" version="2"/>
+  </delete>
+</osmChange>
+"#;
+
+    #[test]
+    fn parses_changes() {
+        let change = parse_osm_change(CHANGE.as_bytes()).unwrap();
+
+        assert_eq!(
+            change.nodes,
+            HashMap::from([
+                (1, Coord { x: 144.9, y: -37.8 }),
+                (
+                    2,
+                    Coord {
+                        x: 144.91,
+                        y: -37.81
+                    }
+                ),
+            ])
+        );
+        assert_eq!(change.deleted_node_ids, vec![4]);
+        assert_eq!(
+            change.ways[0],
+            ChangedWay {
+                id: 10,
+                refs: vec![1, 2],
+                tags: vec![
+                    ("highway".to_string(), "residential".to_string()),
+                    ("name".to_string(), "Smith & Sons Lane".to_string()),
+                ],
+            }
+        );
+        assert_eq!(change.deleted_way_ids, vec![12]);
+        assert_eq!(change.changed_way_ids(), vec![10, 11, 12]);
+    }
+
+    #[test]
+    fn only_cyclable_ways_are_kept() {
+        let change = parse_osm_change(CHANGE.as_bytes()).unwrap();
+        let cyclable = change.cyclable_ways(&WayRules::default());
+
+        assert!(cyclable.ways.contains_key(&10));
+        assert!(!cyclable.ways.contains_key(&11));
+        assert!(cyclable.graph.contains_edge(1, 2));
+        assert!(!cyclable.graph.contains_node(3));
+    }
+}
@@ -1,92 +1,250 @@
+use crate::{rules::WayRules, turns::TurnRestriction};
 use anyhow::Result;
 use geo::Coord;
 use itertools::Itertools;
-use osmpbf::{reader::ElementReader, Element, TagIter};
-use petgraph::prelude::{GraphMap, UnGraphMap};
+use osmpbf::{reader::ElementReader, Element};
+use petgraph::prelude::DiGraphMap;
 use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
 
-/// Creates an undirected, unweighted graph from all ways in an Open Street Maps PBF.
-pub fn get_unweighted_cyclable_graphmap_from_elements(path: &Path) -> Result<UnGraphMap<i64, ()>> {
+/// An edge between two consecutive nodes of a way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WayEdge {
+    /// Only rideable from the source node to the target node.
+    pub oneway: bool,
+    /// The way this edge came from, missing for edges stored before ways were kept.
+    pub way_id: Option<i64>,
+}
+
+/// Road attributes of a way, kept for routing and output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WayTags {
+    pub highway: Option<String>,
+    pub name: Option<String>,
+    pub surface: Option<String>,
+    pub smoothness: Option<String>,
+    pub tracktype: Option<String>,
+    pub maxspeed: Option<String>,
+    pub lit: Option<String>,
+    pub access: Option<String>,
+    pub bicycle: Option<String>,
+}
+
+impl WayTags {
+    pub fn from_tags<'a>(tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut way_tags = Self::default();
+
+        for (key, value) in tags {
+            let field = match key {
+                "highway" => &mut way_tags.highway,
+                "name" => &mut way_tags.name,
+                "surface" => &mut way_tags.surface,
+                "smoothness" => &mut way_tags.smoothness,
+                "tracktype" => &mut way_tags.tracktype,
+                "maxspeed" => &mut way_tags.maxspeed,
+                "lit" => &mut way_tags.lit,
+                "access" => &mut way_tags.access,
+                "bicycle" => &mut way_tags.bicycle,
+                _ => continue,
+            };
+
+            *field = Some(value.to_string());
+        }
+
+        way_tags
+    }
+}
+
+/// The cyclable ways in a map, as a graph of their nodes and the tags of each way,
+/// along with the turn restrictions between ways.
+#[derive(Debug, Default)]
+pub struct CyclableWays {
+    pub graph: DiGraphMap<i64, WayEdge>,
+    pub ways: HashMap<i64, WayTags>,
+    pub restrictions: Vec<TurnRestriction>,
+    /// Edges of ways that share a pair of nodes with an edge already in the graph,
+    /// kept so the edge survives when only one of those ways is deleted.
+    pub shared_edges: Vec<(i64, i64, WayEdge)>,
+}
+
+impl CyclableWays {
+    /// The graph and tags of a single way, which is empty when the rules don't count it as cyclable.
+    pub fn from_way(
+        way_id: i64,
+        refs: impl IntoIterator<Item = i64>,
+        tags: &[(&str, &str)],
+        rules: &WayRules,
+    ) -> Self {
+        let mut cyclable = Self::default();
+
+        if !rules.is_cyclable(tags) {
+            return cyclable;
+        }
+
+        let direction = direction(tags);
+        let edge_way_id = Some(way_id);
+
+        for (source, target) in refs.into_iter().tuple_windows() {
+            let (source, target, oneway) = match direction {
+                Direction::Both => (source.min(target), source.max(target), false),
+                Direction::Forward => (source, target, true),
+                Direction::Backward => (target, source, true),
+            };
+
+            cyclable.insert_edge(
+                source,
+                target,
+                WayEdge {
+                    oneway,
+                    way_id: edge_way_id,
+                },
+            );
+        }
+
+        cyclable
+            .ways
+            .insert(way_id, WayTags::from_tags(tags.iter().copied()));
+
+        cyclable
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        for (source, target, edge) in other.graph.all_edges() {
+            self.insert_edge(source, target, *edge);
+        }
+
+        self.ways.extend(other.ways);
+        self.restrictions.extend(other.restrictions);
+        self.shared_edges.extend(other.shared_edges);
+    }
+
+    /// Adds an edge, where two-way wins when ways share the same pair of nodes
+    /// and the first way stays the origin of the edge.
+    fn insert_edge(&mut self, source: i64, target: i64, edge: WayEdge) {
+        match self.graph.edge_weight_mut(source, target) {
+            Some(existing) => {
+                existing.oneway &= edge.oneway;
+                existing.way_id = existing.way_id.or(edge.way_id);
+                self.shared_edges.push((source, target, edge));
+            }
+            None => {
+                self.graph.add_edge(source, target, edge);
+            }
+        }
+    }
+}
+
+/// The directions a way may be ridden, relative to the order of its nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Both,
+    Forward,
+    Backward,
+}
+
+/// Reads all ways in an Open Street Maps PBF that the rules count as cyclable
+/// into a directed graph.
+///
+/// Two-way edges are stored once, from the smaller node id to the larger one.
+pub fn read_cyclable_ways(path: &Path, rules: &WayRules) -> Result<CyclableWays> {
     let pbf = ElementReader::new(BufReader::with_capacity(1024 * 1024, File::open(path)?));
 
     // Bulk inserts
     // https://github.com/launchbadge/sqlx/blob/main/FAQ.md#how-can-i-bind-an-array-to-a-values-clause-how-can-i-do-bulk-inserts
-    let graph = pbf.par_map_reduce(
-        get_cyclable_node_ids_from_element,
-        || GraphMap::default(),
+    let ways = pbf.par_map_reduce(
+        |element| get_cyclable_ways_from_element(element, rules),
+        CyclableWays::default,
         |mut accu, curr| {
-            accu.extend(curr.all_edges());
+            accu.extend(curr);
             accu
         },
     )?;
 
-    Ok(graph)
+    Ok(ways)
 }
 
-/// Creates an undirected `GraphMap` when an element is a way.
-fn get_cyclable_node_ids_from_element(element: Element<'_>) -> UnGraphMap<i64, ()> {
-    match element {
-        Element::Way(way) => Some(way),
-        _ => None,
-    }
-    .filter(|way| contains_cycleable_tags(way.tags()))
-    .map(|way| {
-        way.refs()
-            .tuple_windows::<(_, _)>()
-            .collect::<UnGraphMap<i64, ()>>()
-    })
-    .unwrap_or_default()
-}
+/// Creates a directed `GraphMap` and the way's tags when an element is a cyclable way,
+/// or the turn restrictions when it's a restriction relation.
+fn get_cyclable_ways_from_element(element: Element<'_>, rules: &WayRules) -> CyclableWays {
+    let mut cyclable = CyclableWays::default();
 
-/// Returns true when a combination of any tags indicate it is cyclable.
-/// Inferred from https://wiki.openstreetmap.org/wiki/Map_features
-fn contains_cycleable_tags(tags: TagIter<'_>) -> bool {
-    let mut highway_footway = false;
-    let mut bicycle_yes = false;
+    let way = match element {
+        Element::Way(way) => way,
+        Element::Relation(relation) => {
+            let tags = relation.tags().collect_vec();
+            let members = relation
+                .members()
+                .filter_map(|member| {
+                    Some((member.role().ok()?, member.member_type, member.member_id))
+                })
+                .collect_vec();
 
-    for tag in tags {
-        match tag {
-            ("highway", "footway") => {
-                highway_footway = true;
-            }
-            ("bicycle", "yes") => {
-                bicycle_yes = true;
-            }
-            _ => {}
-        }
+            cyclable.restrictions = TurnRestriction::from_relation(relation.id(), &tags, &members);
 
-        if highway_footway && bicycle_yes {
-            return true;
+            return cyclable;
         }
+        _ => return cyclable,
+    };
 
-        if cyclable_way(tag) {
-            return true;
-        }
+    let tags = way.tags().collect_vec();
+
+    CyclableWays::from_way(way.id(), way.refs(), &tags, rules)
+}
+
+/// Reads the direction a bicycle may ride a way from its tags.
+/// Inferred from https://wiki.openstreetmap.org/wiki/Key:oneway
+fn direction(tags: &[(&str, &str)]) -> Direction {
+    let value = |key: &str| {
+        tags.iter()
+            .find(|(tag_key, _)| *tag_key == key)
+            .map(|(_, value)| *value)
+    };
+
+    let parse = |value: &str| match value {
+        "yes" | "true" | "1" => Some(Direction::Forward),
+        "-1" | "reverse" => Some(Direction::Backward),
+        "no" | "false" | "0" => Some(Direction::Both),
+        _ => None,
+    };
+
+    // Contraflow lanes let bicycles ride against the traffic.
+    let contraflow = [
+        "cycleway",
+        "cycleway:left",
+        "cycleway:right",
+        "cycleway:both",
+    ]
+    .into_iter()
+    .filter_map(value)
+    .any(|value| value.starts_with("opposite"));
+
+    if let Some(direction) = value("oneway:bicycle").and_then(parse) {
+        return direction;
+    }
+
+    if contraflow {
+        return Direction::Both;
+    }
+
+    if let Some(direction) = value("oneway").and_then(parse) {
+        return direction;
     }
 
-    false
+    match (value("junction"), value("highway")) {
+        (Some("roundabout" | "circular"), _) | (_, Some("motorway")) => Direction::Forward,
+        _ => Direction::Both,
+    }
 }
 
-/// Returns true when a tag for a way is cyclable.
-/// Inferred from https://wiki.openstreetmap.org/wiki/Map_features
-fn cyclable_way(pair: (&str, &str)) -> bool {
-    matches!(
-        pair,
-        (
-            "highway",
-            "trunk"
-                | "primary"
-                | "secondary"
-                | "tertiary"
-                | "residential"
-                | "living_street"
-                | "service"
-                | "pedestrian"
-                | "road"
-                | "cycleway"
-        ) | ("cycleway", _)
-            | ("bicycle_road", "yes")
-    )
+/// Reads the cyclable ways, then the coordinates of only the nodes they use in a second
+/// pass over the PBF, so the coordinates of every other node in the map are never held.
+pub fn read_cyclable_ways_with_coords(
+    path: &Path,
+    rules: &WayRules,
+) -> Result<(CyclableWays, HashMap<i64, Coord>)> {
+    let cyclable = read_cyclable_ways(path, rules)?;
+    let coords = read_to_nodes_coord(path, |node_id| cyclable.graph.contains_node(*node_id))?;
+
+    Ok((cyclable, coords))
 }
 
 pub fn read_to_nodes_coord(
@@ -112,7 +270,7 @@ pub fn read_to_nodes_coord(
             })
             .unwrap_or_default()
         },
-        || HashMap::default(),
+        HashMap::default,
         |mut accu, curr| {
             accu.extend(curr);
             accu
@@ -121,3 +279,78 @@ pub fn read_to_nodes_coord(
 
     Ok(hashmap)
 }
+
+#[cfg(test)]
+mod test {
+    use crate::osm::{direction, CyclableWays, Direction, WayEdge, WayTags};
+
+    #[test]
+    fn two_way_by_default() {
+        assert_eq!(direction(&[("highway", "residential")]), Direction::Both);
+        assert_eq!(direction(&[("oneway", "no")]), Direction::Both);
+    }
+
+    #[test]
+    fn oneway_tags() {
+        assert_eq!(direction(&[("oneway", "yes")]), Direction::Forward);
+        assert_eq!(direction(&[("oneway", "-1")]), Direction::Backward);
+        assert_eq!(direction(&[("junction", "roundabout")]), Direction::Forward);
+    }
+
+    #[test]
+    fn bicycles_may_ride_against_oneway() {
+        let tags = [("oneway", "yes"), ("oneway:bicycle", "no")];
+        assert_eq!(direction(&tags), Direction::Both);
+
+        let tags = [("oneway", "yes"), ("cycleway", "opposite_lane")];
+        assert_eq!(direction(&tags), Direction::Both);
+
+        let tags = [("oneway", "-1"), ("cycleway:left", "opposite_track")];
+        assert_eq!(direction(&tags), Direction::Both);
+    }
+
+    #[test]
+    fn oneway_bicycle_overrides_contraflow() {
+        let tags = [("cycleway", "opposite"), ("oneway:bicycle", "yes")];
+        assert_eq!(direction(&tags), Direction::Forward);
+    }
+
+    #[test]
+    fn two_way_wins_when_merging() {
+        let mut cyclable = CyclableWays::default();
+        let edge = |oneway, way_id| WayEdge {
+            oneway,
+            way_id: Some(way_id),
+        };
+
+        cyclable.insert_edge(1, 2, edge(true, 10));
+        cyclable.insert_edge(1, 2, edge(false, 11));
+        cyclable.insert_edge(1, 2, edge(true, 12));
+
+        assert_eq!(cyclable.graph.edge_weight(1, 2), Some(&edge(false, 10)));
+        assert_eq!(
+            cyclable.shared_edges,
+            vec![(1, 2, edge(false, 11)), (1, 2, edge(true, 12))]
+        );
+    }
+
+    #[test]
+    fn way_tags_keep_road_attributes() {
+        let tags = WayTags::from_tags([
+            ("highway", "tertiary"),
+            ("name", "Mountain Road"),
+            ("surface", "asphalt"),
+            ("lanes", "2"),
+        ]);
+
+        assert_eq!(
+            tags,
+            WayTags {
+                highway: Some("tertiary".to_string()),
+                name: Some("Mountain Road".to_string()),
+                surface: Some("asphalt".to_string()),
+                ..WayTags::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{circuit::Circuit, geojson::write_geojson, gpx::write_gpx};
+use anyhow::{anyhow, Result};
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// File formats a circuit can be written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Gpx,
+    GeoJson,
+}
+
+impl OutputFormat {
+    /// Chooses the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("gpx") => Ok(Self::Gpx),
+            Some("geojson" | "json") => Ok(Self::GeoJson),
+            _ => Err(anyhow!(
+                "Expected {:?} to end with .gpx, .geojson or .json",
+                path
+            )),
+        }
+    }
+}
+
+pub fn write_circuit(path: &Path, circuit: &Circuit, name: &str) -> Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    let writer = BufWriter::new(File::create(path)?);
+
+    match format {
+        OutputFormat::Gpx => write_gpx(writer, circuit, name),
+        OutputFormat::GeoJson => write_geojson(writer, circuit, name),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::OutputFormat;
+    use std::path::Path;
+
+    #[test]
+    fn format_from_extension() {
+        let format = |path: &str| OutputFormat::from_path(Path::new(path)).ok();
+
+        assert_eq!(format("route.gpx"), Some(OutputFormat::Gpx));
+        assert_eq!(format("route.GPX"), Some(OutputFormat::Gpx));
+        assert_eq!(format("route.geojson"), Some(OutputFormat::GeoJson));
+        assert_eq!(format("route.json"), Some(OutputFormat::GeoJson));
+        assert_eq!(format("route.kml"), None);
+        assert_eq!(format("route"), None);
+    }
+}
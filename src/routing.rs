@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use petgraph::{algo::Measure, prelude::DiGraphMap};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+/// Finds the cheapest path from `origin` to `target`, returning the total cost
+/// and every node visited along the way, including both ends.
+///
+/// The search settles the edges ridden rather than the nodes, so every turn
+/// from one edge onto the next can be checked with `can_turn`. The predecessor
+/// of each edge is recorded, so the path follows the shortest-path tree rather
+/// than guessing from the final costs.
+///
+/// When the path carries on from an edge ridden to reach `origin`, pass it as
+/// `arriving` so the first turn out of `origin` is checked too.
+pub fn shortest_path<E, K>(
+    graph: &DiGraphMap<i64, E>,
+    origin: i64,
+    target: i64,
+    arriving: Option<(i64, i64)>,
+    mut cost: impl FnMut((i64, i64, &E)) -> K,
+    can_turn: impl Fn((i64, i64, &E), (i64, i64, &E)) -> bool,
+) -> Result<(K, Vec<i64>)>
+where
+    K: Measure + Copy,
+{
+    if !graph.contains_node(origin) {
+        return Err(anyhow!("Expected to find origin {} in the graph", origin));
+    }
+
+    if !graph.contains_node(target) {
+        return Err(anyhow!("Expected to find target {} in the graph", target));
+    }
+
+    let arriving = match arriving {
+        Some((source, node)) if node == origin => {
+            let weight = graph.edge_weight(source, node).ok_or_else(|| {
+                anyhow!("Expected to find edge {} -> {} in the graph", source, node)
+            })?;
+            Some((source, node, weight))
+        }
+        Some((source, node)) => {
+            return Err(anyhow!(
+                "Expected edge {} -> {} to arrive at origin {}",
+                source,
+                node,
+                origin
+            ))
+        }
+        None => None,
+    };
+
+    if origin == target {
+        return Ok((K::default(), vec![origin]));
+    }
+
+    let mut costs: HashMap<(i64, i64), K> = HashMap::new();
+    let mut predecessors: HashMap<(i64, i64), (i64, i64)> = HashMap::new();
+    let mut settled: HashSet<(i64, i64)> = HashSet::new();
+    let mut visit = BinaryHeap::new();
+
+    for edge in graph.edges(origin) {
+        if arriving.is_some_and(|incoming| !can_turn(incoming, edge)) {
+            continue;
+        }
+
+        let edge_cost = cost(edge);
+
+        if costs
+            .get(&(edge.0, edge.1))
+            .is_none_or(|known| edge_cost < *known)
+        {
+            costs.insert((edge.0, edge.1), edge_cost);
+            visit.push(MinScored(edge_cost, (edge.0, edge.1)));
+        }
+    }
+
+    while let Some(MinScored(path_cost, (source, node))) = visit.pop() {
+        if !settled.insert((source, node)) {
+            continue;
+        }
+
+        if node == target {
+            let mut path = vec![node, source];
+            let mut current = (source, node);
+
+            while let Some(previous) = predecessors.get(&current) {
+                path.push(previous.0);
+                current = *previous;
+            }
+
+            path.reverse();
+
+            return Ok((path_cost, path));
+        }
+
+        let incoming = (source, node, &graph[(source, node)]);
+
+        for outgoing in graph.edges(node) {
+            let next = (outgoing.0, outgoing.1);
+
+            if settled.contains(&next) || !can_turn(incoming, outgoing) {
+                continue;
+            }
+
+            let next_cost = path_cost + cost(outgoing);
+
+            if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                continue;
+            }
+
+            costs.insert(next, next_cost);
+            predecessors.insert(next, (source, node));
+            visit.push(MinScored(next_cost, next));
+        }
+    }
+
+    Err(anyhow!(
+        "Expected to find a path from {} to {}",
+        origin,
+        target
+    ))
+}
+
+/// Orders the cheapest cost first in a `BinaryHeap`.
+struct MinScored<K, T>(K, T);
+
+impl<K: PartialOrd, T> PartialEq for MinScored<K, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K: PartialOrd, T> Eq for MinScored<K, T> {}
+
+impl<K: PartialOrd, T> PartialOrd for MinScored<K, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: PartialOrd, T> Ord for MinScored<K, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::routing::shortest_path;
+    use petgraph::prelude::DiGraphMap;
+
+    #[test]
+    fn follows_cheapest_path() {
+        // 1 -> 2 -> 4 costs 10, 1 -> 3 -> 4 costs 3.
+        let graph: DiGraphMap<i64, i64> =
+            DiGraphMap::from_edges([(1, 2, 5), (2, 4, 5), (1, 3, 1), (3, 4, 2)]);
+
+        let (cost, path) =
+            shortest_path(&graph, 1, 4, None, |(_, _, cost)| *cost, |_, _| true).unwrap();
+
+        assert_eq!(cost, 3);
+        assert_eq!(path, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn origin_is_target() {
+        let graph: DiGraphMap<i64, i64> = DiGraphMap::from_edges([(1, 2, 5)]);
+
+        let (cost, path) =
+            shortest_path(&graph, 1, 1, None, |(_, _, cost)| *cost, |_, _| true).unwrap();
+
+        assert_eq!(cost, 0);
+        assert_eq!(path, vec![1]);
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let graph: DiGraphMap<i64, i64> = DiGraphMap::from_edges([(1, 2, 5), (3, 2, 5)]);
+
+        assert!(shortest_path(&graph, 1, 3, None, |(_, _, cost)| *cost, |_, _| true).is_err());
+    }
+
+    #[test]
+    fn missing_node() {
+        let graph: DiGraphMap<i64, i64> = DiGraphMap::from_edges([(1, 2, 5)]);
+
+        assert!(shortest_path(&graph, 1, 9, None, |(_, _, cost)| *cost, |_, _| true).is_err());
+    }
+
+    #[test]
+    fn respects_turns() {
+        // 1 -> 2 -> 4 is cheapest, but turning at 2 is forbidden.
+        let graph: DiGraphMap<i64, i64> =
+            DiGraphMap::from_edges([(1, 2, 1), (2, 4, 1), (1, 3, 5), (3, 4, 5)]);
+
+        let (cost, path) = shortest_path(
+            &graph,
+            1,
+            4,
+            None,
+            |(_, _, cost)| *cost,
+            |(source, via, _), (_, target, _)| (source, via, target) != (1, 2, 4),
+        )
+        .unwrap();
+
+        assert_eq!(cost, 10);
+        assert_eq!(path, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn revisits_nodes_to_avoid_turns() {
+        // turning left from 1 onto 3 at 2 is forbidden, so loop around the block through 5.
+        let graph: DiGraphMap<i64, i64> =
+            DiGraphMap::from_edges([(1, 2, 1), (2, 3, 1), (2, 5, 1), (5, 6, 1), (6, 2, 1)]);
+
+        let (cost, path) = shortest_path(
+            &graph,
+            1,
+            3,
+            None,
+            |(_, _, cost)| *cost,
+            |(source, _, _), (_, target, _)| (source, target) != (1, 3),
+        )
+        .unwrap();
+
+        assert_eq!(cost, 5);
+        assert_eq!(path, vec![1, 2, 5, 6, 2, 3]);
+    }
+
+    #[test]
+    fn checks_the_turn_out_of_the_origin() {
+        // arriving at 2 from 1, turning back onto 1 is forbidden, so carry on around through 3.
+        let graph: DiGraphMap<i64, i64> =
+            DiGraphMap::from_edges([(1, 2, 1), (2, 1, 1), (2, 3, 1), (3, 1, 1)]);
+
+        let (cost, path) = shortest_path(
+            &graph,
+            2,
+            1,
+            Some((1, 2)),
+            |(_, _, cost)| *cost,
+            |(source, _, _), (_, target, _)| source != target,
+        )
+        .unwrap();
+
+        assert_eq!(cost, 2);
+        assert_eq!(path, vec![2, 3, 1]);
+        assert!(shortest_path(&graph, 2, 1, Some((3, 1)), |_| 1, |_, _| true).is_err());
+    }
+}
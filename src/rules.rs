@@ -0,0 +1,380 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Rules that ship with the binary, used when no rules file is given.
+const DEFAULT_RULES: &str = include_str!("../rules/cyclable.toml");
+
+#[derive(Debug, Args, Clone)]
+pub struct RulesArgs {
+    /// TOML file deciding which ways are cyclable, like `rules/cyclable.toml` which is used by default.
+    #[arg(long)]
+    pub rules: Option<PathBuf>,
+}
+
+impl RulesArgs {
+    /// Reads the rules from the file given, or uses the default rules without one.
+    pub fn load(&self) -> Result<WayRules> {
+        WayRules::load(self.rules.as_deref())
+    }
+}
+
+/// Decides which ways in a map count as cyclable, loaded from a TOML file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WayRules {
+    /// A way is cyclable when it matches any of these expressions.
+    pub include: Vec<TagExpr>,
+    /// Unless it also matches any of these.
+    #[serde(default)]
+    pub exclude: Vec<TagExpr>,
+    #[serde(default)]
+    pub access: AccessRules,
+}
+
+/// Which restricted ways may still be ridden.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessRules {
+    /// Ride ways with `access=private`.
+    #[serde(default)]
+    pub private: bool,
+    /// Ride ways with `bicycle=dismount`.
+    #[serde(default)]
+    pub dismount: bool,
+}
+
+/// Terms that must all match the tags of a way, written as `key=a|b & key!=c`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TagExpr {
+    terms: Vec<TagTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TagTerm {
+    key: String,
+    /// Any value matches when missing.
+    values: Option<Vec<String>>,
+    negated: bool,
+}
+
+impl WayRules {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Expected to read way rules from {:?}", path))?;
+
+        contents
+            .parse()
+            .with_context(|| format!("Expected valid way rules in {:?}", path))
+    }
+
+    /// Reads the rules from a file, or uses the default rules without one.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        path.map_or_else(|| Ok(Self::default()), Self::from_path)
+    }
+
+    /// Returns true when the tags of a way make it cyclable.
+    pub fn is_cyclable(&self, tags: &[(&str, &str)]) -> bool {
+        self.include.iter().any(|expr| expr.matches(tags))
+            && !self.exclude.iter().any(|expr| expr.matches(tags))
+            && self.access.permits(tags)
+    }
+}
+
+impl Default for WayRules {
+    fn default() -> Self {
+        DEFAULT_RULES
+            .parse()
+            .expect("Expected the default way rules to be valid")
+    }
+}
+
+impl FromStr for WayRules {
+    type Err = anyhow::Error;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// Whether a bicycle may use a way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Allowed,
+    Private,
+    Dismount,
+    Denied,
+}
+
+impl Access {
+    /// Evaluates the access tags of a way for a bicycle, where the most specific
+    /// tag wins: `bicycle`, then `vehicle`, then `motorroad`, then `access`.
+    ///
+    /// Values that don't decide access for a bicycle fall through to the next tag.
+    /// Inferred from https://wiki.openstreetmap.org/wiki/Key:access
+    pub fn from_tags(tags: &[(&str, &str)]) -> Self {
+        let value = |key: &str| {
+            tags.iter()
+                .find(|(tag_key, _)| *tag_key == key)
+                .map(|(_, value)| *value)
+        };
+
+        let parse = |value: &str| match value {
+            "yes" | "designated" | "permissive" | "destination" | "official" | "discouraged" => {
+                Some(Access::Allowed)
+            }
+            "private" => Some(Access::Private),
+            "dismount" => Some(Access::Dismount),
+            "no" | "use_sidepath" | "customers" | "delivery" | "agricultural" | "forestry" => {
+                Some(Access::Denied)
+            }
+            _ => None,
+        };
+
+        // motorroads are only for motor vehicles
+        let motorroad = match value("motorroad") {
+            Some("yes") => Some(Access::Denied),
+            _ => None,
+        };
+
+        value("bicycle")
+            .and_then(parse)
+            .or_else(|| value("vehicle").and_then(parse))
+            .or(motorroad)
+            .or_else(|| value("access").and_then(parse))
+            .unwrap_or(Access::Allowed)
+    }
+}
+
+impl AccessRules {
+    fn permits(&self, tags: &[(&str, &str)]) -> bool {
+        match Access::from_tags(tags) {
+            Access::Allowed => true,
+            Access::Private => self.private,
+            Access::Dismount => self.dismount,
+            Access::Denied => false,
+        }
+    }
+}
+
+impl TagExpr {
+    pub fn matches(&self, tags: &[(&str, &str)]) -> bool {
+        self.terms.iter().all(|term| term.matches(tags))
+    }
+}
+
+impl TagTerm {
+    fn matches(&self, tags: &[(&str, &str)]) -> bool {
+        let found = tags
+            .iter()
+            .find(|(key, _)| *key == self.key)
+            .is_some_and(|(_, value)| {
+                self.values
+                    .as_ref()
+                    .is_none_or(|values| values.iter().any(|expected| expected == value))
+            });
+
+        found != self.negated
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(expr: &str) -> Result<Self> {
+        let terms = expr
+            .split('&')
+            .map(str::trim)
+            .map(|term| -> Result<TagTerm> {
+                let (key, values, negated) = match term.split_once('=') {
+                    None => (term, None, false),
+                    Some((key, values)) => match key.strip_suffix('!') {
+                        Some(key) => (key, Some(values), true),
+                        None => (key, Some(values), false),
+                    },
+                };
+
+                let key = key.trim();
+
+                if key.is_empty() {
+                    return Err(anyhow!("Expected a key in the term {:?}", term));
+                }
+
+                let values = values.map(|values| {
+                    values
+                        .split('|')
+                        .map(|value| value.trim().to_string())
+                        .collect()
+                });
+
+                Ok(TagTerm {
+                    key: key.to_string(),
+                    values,
+                    negated,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(TagExpr { terms })
+    }
+}
+
+impl TryFrom<String> for TagExpr {
+    type Error = anyhow::Error;
+
+    fn try_from(expr: String) -> Result<Self> {
+        expr.parse()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rules::{Access, AccessRules, TagExpr, WayRules};
+
+    #[test]
+    fn parses_terms() {
+        let expr: TagExpr = "highway=footway|path & bicycle!=no & lit".parse().unwrap();
+
+        assert!(expr.matches(&[("highway", "path"), ("lit", "yes")]));
+        assert!(!expr.matches(&[("highway", "path"), ("bicycle", "no"), ("lit", "yes")]));
+        assert!(!expr.matches(&[("highway", "path")]));
+        assert!(!expr.matches(&[("highway", "primary"), ("lit", "yes")]));
+    }
+
+    #[test]
+    fn rejects_missing_keys() {
+        assert!("=yes".parse::<TagExpr>().is_err());
+        assert!("highway=primary & ".parse::<TagExpr>().is_err());
+    }
+
+    #[test]
+    fn default_rules() {
+        let rules = WayRules::default();
+
+        assert!(rules.is_cyclable(&[("highway", "residential")]));
+        assert!(rules.is_cyclable(&[("highway", "footway"), ("bicycle", "yes")]));
+        assert!(!rules.is_cyclable(&[("highway", "footway")]));
+        assert!(!rules.is_cyclable(&[("highway", "motorway")]));
+        assert!(!rules.is_cyclable(&[("highway", "residential"), ("access", "private")]));
+        assert!(!rules.is_cyclable(&[("highway", "cycleway"), ("bicycle", "dismount")]));
+    }
+
+    #[test]
+    fn rules_from_toml() {
+        let rules: WayRules = r#"
+            include = ["highway=track|path"]
+            exclude = ["surface=sand"]
+
+            [access]
+            private = true
+        "#
+        .parse()
+        .unwrap();
+
+        assert!(rules.is_cyclable(&[("highway", "track"), ("access", "private")]));
+        assert!(!rules.is_cyclable(&[("highway", "track"), ("surface", "sand")]));
+        assert!(!rules.is_cyclable(&[("highway", "residential")]));
+    }
+
+    #[test]
+    fn unknown_fields_are_an_error() {
+        assert!("include = []\nexlcude = []".parse::<WayRules>().is_err());
+    }
+
+    #[test]
+    fn access_defaults_to_allowed() {
+        assert_eq!(Access::from_tags(&[]), Access::Allowed);
+        assert_eq!(
+            Access::from_tags(&[("highway", "residential")]),
+            Access::Allowed
+        );
+        assert_eq!(
+            Access::from_tags(&[("access", "destination")]),
+            Access::Allowed
+        );
+    }
+
+    #[test]
+    fn negative_access_tags() {
+        let denied = [
+            vec![("access", "no")],
+            vec![("bicycle", "no")],
+            vec![("motorroad", "yes")],
+            vec![("vehicle", "no")],
+            vec![("access", "yes"), ("bicycle", "no")],
+            vec![("access", "yes"), ("motorroad", "yes")],
+            vec![("bicycle", "use_sidepath")],
+        ];
+
+        for tags in denied {
+            assert_eq!(Access::from_tags(&tags), Access::Denied, "{:?}", tags);
+        }
+
+        assert_eq!(Access::from_tags(&[("access", "private")]), Access::Private);
+        assert_eq!(
+            Access::from_tags(&[("bicycle", "dismount")]),
+            Access::Dismount
+        );
+    }
+
+    #[test]
+    fn most_specific_access_tag_wins() {
+        let allowed = [
+            vec![("access", "no"), ("bicycle", "yes")],
+            vec![("access", "private"), ("bicycle", "designated")],
+            vec![("motorroad", "yes"), ("bicycle", "yes")],
+            vec![("access", "no"), ("vehicle", "yes")],
+            // unknown values fall through to the next tag
+            vec![("bicycle", "unknown"), ("access", "yes")],
+        ];
+
+        for tags in allowed {
+            assert_eq!(Access::from_tags(&tags), Access::Allowed, "{:?}", tags);
+        }
+
+        assert_eq!(
+            Access::from_tags(&[("vehicle", "yes"), ("bicycle", "no")]),
+            Access::Denied
+        );
+        assert_eq!(
+            Access::from_tags(&[("access", "yes"), ("vehicle", "private")]),
+            Access::Private
+        );
+    }
+
+    #[test]
+    fn access_rules_decide_private_and_dismount() {
+        let strict = AccessRules::default();
+        let relaxed = AccessRules {
+            private: true,
+            dismount: true,
+        };
+
+        for tags in [[("access", "private")], [("bicycle", "dismount")]] {
+            assert!(!strict.permits(&tags));
+            assert!(relaxed.permits(&tags));
+        }
+
+        assert!(!relaxed.permits(&[("access", "no")]));
+    }
+
+    #[test]
+    fn restricted_ways_are_never_cyclable() {
+        let rules = WayRules::default();
+
+        assert!(!rules.is_cyclable(&[("highway", "residential"), ("bicycle", "no")]));
+        assert!(!rules.is_cyclable(&[("highway", "trunk"), ("motorroad", "yes")]));
+        assert!(!rules.is_cyclable(&[("cycleway", "lane"), ("access", "no")]));
+        assert!(rules.is_cyclable(&[
+            ("highway", "service"),
+            ("access", "private"),
+            ("bicycle", "yes")
+        ]));
+    }
+}
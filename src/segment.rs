@@ -0,0 +1,178 @@
+use geo::{Coord, Distance, Haversine};
+
+/// A directed edge between two nodes that have elevations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    /// Metres between both nodes.
+    pub distance: f64,
+    /// Rise over run, positive when climbing from source to target.
+    pub gradient: f64,
+    /// Metres climbed from source to target, more than the rise when the segment dips along the way.
+    pub climbing: f64,
+    /// Metres descended from source to target.
+    pub descending: f64,
+    /// Steepest climb anywhere along the segment, as rise over run.
+    pub max_gradient: f64,
+    /// Steepest descent anywhere along the segment, as rise over run.
+    pub min_gradient: f64,
+}
+
+/// Elevation changes along an edge from source to target, sampled from a DEM between its nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeProfile {
+    /// Metres climbed.
+    pub climbing: f64,
+    /// Metres descended.
+    pub descending: f64,
+    /// Steepest rise over run between two samples.
+    pub max_gradient: f64,
+    /// Steepest fall between two samples, as a negative rise over run.
+    pub min_gradient: f64,
+}
+
+impl Segment {
+    /// Creates the segment travelling from `source` to `target`,
+    /// where each is a coordinate and an elevation in metres.
+    pub fn between(source: (Coord, f64), target: (Coord, f64)) -> Self {
+        Self::along(source, target, None)
+    }
+
+    /// Creates the segment travelling from `source` to `target`, climbing as its profile
+    /// does when there is one and otherwise steadily from one elevation to the other.
+    ///
+    /// The rise comes from the profile too, since its samples needn't agree with the
+    /// elevations of the nodes.
+    pub fn along(
+        source: (Coord, f64),
+        target: (Coord, f64),
+        profile: Option<&EdgeProfile>,
+    ) -> Self {
+        let distance = Haversine::distance(source.0.into(), target.0.into());
+
+        let profile = profile
+            .copied()
+            .unwrap_or_else(|| EdgeProfile::from_elevations(distance, &[source.1, target.1]));
+
+        // Nodes sharing a coordinate would otherwise divide by zero.
+        let gradient = if distance > 0.0 {
+            (profile.climbing - profile.descending) / distance
+        } else {
+            0.0
+        };
+
+        Self {
+            distance,
+            gradient,
+            climbing: profile.climbing,
+            descending: profile.descending,
+            max_gradient: profile.max_gradient,
+            min_gradient: profile.min_gradient,
+        }
+    }
+
+    /// The same segment travelled from target to source.
+    pub fn reversed(&self) -> Self {
+        Self {
+            distance: self.distance,
+            gradient: -self.gradient,
+            climbing: self.descending,
+            descending: self.climbing,
+            max_gradient: -self.min_gradient,
+            min_gradient: -self.max_gradient,
+        }
+    }
+}
+
+impl EdgeProfile {
+    /// Summarises elevations sampled at even intervals over `distance` metres,
+    /// including both ends.
+    pub fn from_elevations(distance: f64, elevations: &[f64]) -> Self {
+        let interval = distance / elevations.len().saturating_sub(1).max(1) as f64;
+
+        let mut profile = Self {
+            climbing: 0.0,
+            descending: 0.0,
+            max_gradient: f64::NEG_INFINITY,
+            min_gradient: f64::INFINITY,
+        };
+
+        for (from, to) in elevations.iter().zip(elevations.iter().skip(1)) {
+            let rise = to - from;
+            let gradient = if interval > 0.0 { rise / interval } else { 0.0 };
+
+            profile.climbing += rise.max(0.0);
+            profile.descending += (-rise).max(0.0);
+            profile.max_gradient = profile.max_gradient.max(gradient);
+            profile.min_gradient = profile.min_gradient.min(gradient);
+        }
+
+        // a single sample has no slope to speak of
+        if elevations.len() < 2 {
+            profile.max_gradient = 0.0;
+            profile.min_gradient = 0.0;
+        }
+
+        profile
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::segment::{EdgeProfile, Segment};
+    use geo::Coord;
+
+    #[test]
+    fn gradient_is_rise_over_run() {
+        let source = (Coord { x: 0.0, y: 0.0 }, 100.0);
+        let target = (Coord { x: 0.0, y: 0.01 }, 110.0);
+
+        let segment = Segment::between(source, target);
+
+        assert!((segment.distance - 1_111.95).abs() < 1.0);
+        assert!((segment.gradient - 10.0 / segment.distance).abs() < f64::EPSILON);
+        assert_eq!(segment.reversed().gradient, -segment.gradient);
+        assert_eq!(segment.climbing, 10.0);
+        assert_eq!(segment.descending, 0.0);
+        assert_eq!(segment.max_gradient, segment.gradient);
+    }
+
+    #[test]
+    fn zero_distance_is_flat() {
+        let coord = Coord { x: 1.0, y: 1.0 };
+
+        let segment = Segment::between((coord, 100.0), (coord, 110.0));
+
+        assert_eq!(segment.distance, 0.0);
+        assert_eq!(segment.gradient, 0.0);
+    }
+
+    #[test]
+    fn profiles_find_valleys() {
+        // 400 m dropping 20 m into a creek and climbing 30 m out of it
+        let profile = EdgeProfile::from_elevations(400.0, &[100.0, 90.0, 80.0, 95.0, 110.0]);
+
+        assert_eq!(
+            profile,
+            EdgeProfile {
+                climbing: 30.0,
+                descending: 20.0,
+                max_gradient: 0.15,
+                min_gradient: -0.1,
+            }
+        );
+
+        // the nodes rise 15 m, but the samples between them only 10 m
+        let source = (Coord { x: 0.0, y: 0.0 }, 100.0);
+        let target = (Coord { x: 0.0, y: 0.0036 }, 115.0);
+        let segment = Segment::along(source, target, Some(&profile));
+
+        assert!((segment.gradient - 10.0 / segment.distance).abs() < f64::EPSILON);
+        assert_eq!(segment.climbing, 30.0);
+
+        let reversed = segment.reversed();
+        assert_eq!(reversed.climbing, 20.0);
+        assert_eq!(reversed.descending, 30.0);
+        assert_eq!(reversed.max_gradient, 0.1);
+        assert_eq!(reversed.min_gradient, -0.15);
+    }
+}
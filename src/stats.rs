@@ -0,0 +1,269 @@
+use crate::{circuit::Circuit, segment::Segment};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+/// Totals for a sequence of nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RouteStats {
+    /// Metres along the ground.
+    pub distance_m: f64,
+    /// Metres gained while climbing.
+    pub climbing_m: f64,
+    /// Metres lost while descending.
+    pub descending_m: f64,
+    /// Steepest climb, as rise over run.
+    pub max_gradient: f64,
+    /// Steepest descent, as rise over run.
+    pub min_gradient: f64,
+    /// Steepness of the whole route regardless of direction, weighted by distance.
+    pub avg_gradient: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HighestPoint {
+    pub node_id: i64,
+    pub x: f64,
+    pub y: f64,
+    pub elevation_m: f64,
+}
+
+/// Summary of a circuit, for judging a route before riding it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CircuitStats {
+    pub ascent: RouteStats,
+    pub descent: RouteStats,
+    pub total: RouteStats,
+    pub highest: HighestPoint,
+}
+
+impl CircuitStats {
+    pub fn from_circuit(circuit: &Circuit) -> Result<Self> {
+        let position = |node_id: &i64| {
+            circuit
+                .nodes
+                .get(node_id)
+                .copied()
+                .ok_or_else(|| anyhow!("Expected to find node {} in the circuit", node_id))
+        };
+
+        let route = |node_ids: &[i64]| -> Result<RouteStats> {
+            let positions: Vec<_> = node_ids.iter().map(position).try_collect()?;
+
+            // edges know how the ground rises and falls between their nodes
+            let segments = node_ids
+                .iter()
+                .zip(positions)
+                .tuple_windows()
+                .map(|((source_node_id, source), (target_node_id, target))| {
+                    circuit
+                        .gradients
+                        .edge_weight(*source_node_id, *target_node_id)
+                        .map_or_else(|| Segment::between(source, target), |edge| edge.segment)
+                })
+                .collect_vec();
+
+            Ok(RouteStats::from_segments(&segments))
+        };
+
+        let (node_id, (coord, elevation_m)) = circuit
+            .node_ids()
+            .map(|node_id| position(&node_id).map(|position| (node_id, position)))
+            .process_results(|positions| {
+                positions.max_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            })?
+            .ok_or_else(|| anyhow!("Expected the circuit to visit at least one node"))?;
+
+        Ok(Self {
+            ascent: route(&circuit.ascent)?,
+            descent: route(&circuit.descent)?,
+            total: route(&circuit.node_ids().collect_vec())?,
+            highest: HighestPoint {
+                node_id,
+                x: coord.x,
+                y: coord.y,
+                elevation_m,
+            },
+        })
+    }
+}
+
+impl RouteStats {
+    pub fn from_segments(segments: &[Segment]) -> Self {
+        let distance_m: f64 = segments.iter().map(|segment| segment.distance).sum();
+
+        let climbing_m = segments.iter().map(|segment| segment.climbing).sum();
+        let descending_m = segments.iter().map(|segment| segment.descending).sum();
+
+        let max_gradient = segments
+            .iter()
+            .map(|segment| segment.max_gradient)
+            .fold(0.0, f64::max);
+
+        let min_gradient = segments
+            .iter()
+            .map(|segment| segment.min_gradient)
+            .fold(0.0, f64::min);
+
+        let avg_gradient = if distance_m > 0.0 {
+            (climbing_m + descending_m) / distance_m
+        } else {
+            0.0
+        };
+
+        Self {
+            distance_m,
+            climbing_m,
+            descending_m,
+            max_gradient,
+            min_gradient,
+            avg_gradient,
+        }
+    }
+}
+
+impl Display for RouteStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} km, +{:.0} m, -{:.0} m, max {:.1}%, min {:.1}%, avg {:.1}%",
+            self.distance_m / 1_000.0,
+            self.climbing_m,
+            self.descending_m,
+            self.max_gradient * 100.0,
+            self.min_gradient * 100.0,
+            self.avg_gradient * 100.0,
+        )
+    }
+}
+
+impl Display for CircuitStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Ascent:  {}", self.ascent)?;
+        writeln!(f, "Descent: {}", self.descent)?;
+        writeln!(f, "Total:   {}", self.total)?;
+        write!(
+            f,
+            "Highest: {:.0} m at {:.5}, {:.5}",
+            self.highest.elevation_m, self.highest.x, self.highest.y
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        circuit::{Circuit, CircuitEdge},
+        segment::{EdgeProfile, Segment},
+        stats::{CircuitStats, RouteStats},
+    };
+    use geo::Coord;
+    use indexmap::IndexMap;
+    use petgraph::prelude::DiGraphMap;
+    use std::collections::HashMap;
+
+    fn steady(distance: f64, gradient: f64) -> Segment {
+        Segment {
+            distance,
+            gradient,
+            climbing: (gradient * distance).max(0.0),
+            descending: (-gradient * distance).max(0.0),
+            max_gradient: gradient,
+            min_gradient: gradient,
+        }
+    }
+
+    #[test]
+    fn route_from_segments() {
+        let stats = RouteStats::from_segments(&[steady(100.0, 0.1), steady(300.0, -0.05)]);
+
+        assert_eq!(stats.distance_m, 400.0);
+        assert!((stats.climbing_m - 10.0).abs() < 1e-9);
+        assert!((stats.descending_m - 15.0).abs() < 1e-9);
+        assert_eq!(stats.max_gradient, 0.1);
+        assert_eq!(stats.min_gradient, -0.05);
+        assert!((stats.avg_gradient - 25.0 / 400.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_route() {
+        let stats = RouteStats::from_segments(&[]);
+
+        assert_eq!(stats.distance_m, 0.0);
+        assert_eq!(stats.avg_gradient, 0.0);
+    }
+
+    #[test]
+    fn circuit_totals_both_legs() {
+        let circuit = Circuit {
+            nodes: IndexMap::from([
+                (2, (Coord { x: 0.0, y: 0.01 }, 50.0)),
+                (1, (Coord { x: 0.0, y: 0.0 }, 0.0)),
+            ]),
+            gradients: DiGraphMap::new(),
+            ways: HashMap::new(),
+            ascent: vec![1, 2],
+            descent: vec![2, 1],
+        };
+
+        let stats = CircuitStats::from_circuit(&circuit).unwrap();
+
+        assert!((stats.ascent.distance_m - 1_111.95).abs() < 1.0);
+        assert_eq!(stats.total.distance_m, stats.ascent.distance_m * 2.0);
+        assert!((stats.total.climbing_m - 50.0).abs() < 1e-9);
+        assert!((stats.total.descending_m - 50.0).abs() < 1e-9);
+        assert_eq!(stats.highest.node_id, 2);
+        assert_eq!(stats.highest.elevation_m, 50.0);
+    }
+
+    #[test]
+    fn edges_climb_as_their_profile_does() {
+        let nodes = IndexMap::from([
+            (2, (Coord { x: 0.0, y: 0.01 }, 50.0)),
+            (1, (Coord { x: 0.0, y: 0.0 }, 0.0)),
+        ]);
+
+        // the road dips 20 m into a gully on the way up
+        let profile = EdgeProfile {
+            climbing: 70.0,
+            descending: 20.0,
+            max_gradient: 0.12,
+            min_gradient: -0.08,
+        };
+        let segment = Segment::along(nodes[&1], nodes[&2], Some(&profile));
+
+        let circuit = Circuit {
+            gradients: DiGraphMap::from_edges([
+                (
+                    1,
+                    2,
+                    CircuitEdge {
+                        segment,
+                        way_id: None,
+                    },
+                ),
+                (
+                    2,
+                    1,
+                    CircuitEdge {
+                        segment: segment.reversed(),
+                        way_id: None,
+                    },
+                ),
+            ]),
+            nodes,
+            ways: HashMap::new(),
+            ascent: vec![1, 2],
+            descent: vec![2, 1],
+        };
+
+        let stats = CircuitStats::from_circuit(&circuit).unwrap();
+
+        assert_eq!(stats.ascent.climbing_m, 70.0);
+        assert_eq!(stats.ascent.descending_m, 20.0);
+        assert_eq!(stats.descent.climbing_m, 20.0);
+        assert_eq!(stats.total.max_gradient, 0.12);
+        assert_eq!(stats.total.min_gradient, -0.12);
+    }
+}
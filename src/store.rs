@@ -0,0 +1,96 @@
+mod copy;
+#[cfg(test)]
+pub mod memory;
+pub mod postgres;
+
+use crate::{
+    area::SearchArea,
+    jobs::{IngestJob, JobStatus, Source, Stage},
+    osm::{WayEdge, WayTags},
+    segment::EdgeProfile,
+    turns::TurnRestriction,
+};
+use anyhow::Result;
+use geo::{Coord, Rect};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+
+/// Persists the cyclable graph between bootstrapping and finding circuits.
+///
+/// Edges are directed, but only oneway edges restrict riding from target to source.
+pub trait GraphStore {
+    /// Inserts nodes without coordinates, ignoring nodes that already exist.
+    async fn insert_node_ids(&self, node_ids: Vec<i64>) -> Result<u64>;
+
+    /// Inserts nodes with their coordinates, replacing the coordinate of nodes that already exist
+    /// and clearing the elevation of those that moved and the profile of their edges.
+    async fn insert_nodes(&self, coords: HashMap<i64, Coord>) -> Result<u64>;
+
+    /// Inserts ways, replacing the tags of ways that already exist.
+    async fn insert_way_tags(&self, ways: HashMap<i64, WayTags>) -> Result<u64>;
+
+    /// Inserts edges between existing nodes and ways, remembering every way an edge belongs to.
+    /// An edge that already exists becomes two-way when either copy is two-way.
+    async fn insert_edges(&self, edges: Vec<(i64, i64, WayEdge)>) -> Result<u64>;
+
+    /// Inserts turn restrictions, ignoring restrictions that already exist.
+    async fn insert_turn_restrictions(&self, restrictions: Vec<TurnRestriction>) -> Result<u64>;
+
+    /// Nodes that are still waiting for a coordinate.
+    async fn query_node_ids(&self) -> Result<HashSet<i64>>;
+
+    /// Sets the coordinate of existing nodes, clearing the elevation of nodes that moved
+    /// and the profile of their edges.
+    async fn update_coordinates(&self, coords: HashMap<i64, Coord>) -> Result<u64>;
+
+    /// Deletes ways along with the edges no remaining way belongs to.
+    /// Edges shared with a remaining way take their way and direction from what remains.
+    async fn delete_ways(&self, way_ids: Vec<i64>) -> Result<u64>;
+
+    /// Deletes nodes along with every edge to or from them.
+    async fn delete_nodes(&self, node_ids: Vec<i64>) -> Result<u64>;
+
+    /// Deletes nodes that no edge goes to or from anymore.
+    async fn delete_orphaned_nodes(&self) -> Result<u64>;
+
+    /// Nodes with a coordinate inside `rect` that are still waiting for an elevation.
+    async fn query_containing_coords(&self, rect: Rect) -> Result<HashMap<i64, Coord>>;
+
+    async fn update_elevations(&self, elevations: Vec<(i64, f64)>) -> Result<u64>;
+
+    /// Edges with either node inside `rect` that are still waiting for a profile,
+    /// along with the coordinates of their source and target.
+    async fn query_unprofiled_edges(&self, rect: Rect) -> Result<Vec<(i64, i64, Coord, Coord)>>;
+
+    async fn update_edge_profiles(&self, profiles: Vec<(i64, i64, EdgeProfile)>) -> Result<u64>;
+
+    /// Nodes with an elevation inside the area, highest first.
+    async fn query_nodes_within(&self, area: &SearchArea) -> Result<IndexMap<i64, (Coord, f64)>>;
+
+    /// Edges where both nodes are in `node_ids`.
+    async fn query_edges_between(&self, node_ids: &[i64]) -> Result<Vec<(i64, i64, WayEdge)>>;
+
+    /// Profiles of the edges where both nodes are in `node_ids`, by source and target.
+    async fn query_edge_profiles(
+        &self,
+        node_ids: &[i64],
+    ) -> Result<HashMap<(i64, i64), EdgeProfile>>;
+
+    async fn query_way_tags(&self, way_ids: &[i64]) -> Result<HashMap<i64, WayTags>>;
+
+    /// Turn restrictions where the via node is in `node_ids`.
+    async fn query_turn_restrictions(&self, node_ids: &[i64]) -> Result<Vec<TurnRestriction>>;
+
+    /// Every bootstrap job that has been started, oldest first.
+    async fn query_jobs(&self) -> Result<Vec<IngestJob>>;
+
+    /// Records that a stage has started for a source, returning the id of the job.
+    async fn insert_job(&self, stage: Stage, source: &Source) -> Result<i64>;
+
+    async fn update_job_status(
+        &self,
+        job_id: i64,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<()>;
+}
@@ -0,0 +1,119 @@
+/// Encodes rows in the binary format read by `COPY ... FROM STDIN (FORMAT binary)`.
+/// https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.4
+#[derive(Debug)]
+pub struct BinaryCopy {
+    buffer: Vec<u8>,
+}
+
+/// A value that can be written as a field of a binary `COPY` row.
+pub trait CopyField {
+    /// Writes the field, or returns false to write `NULL`.
+    fn write(&self, buffer: &mut Vec<u8>) -> bool;
+}
+
+impl BinaryCopy {
+    const SIGNATURE: &'static [u8] = b"PGCOPY\n\xff\r\n\0";
+
+    pub fn row(&mut self, fields: &[&dyn CopyField]) {
+        self.buffer
+            .extend_from_slice(&(fields.len() as i16).to_be_bytes());
+
+        for field in fields {
+            let length_at = self.buffer.len();
+            self.buffer.extend_from_slice(&(-1i32).to_be_bytes());
+
+            if field.write(&mut self.buffer) {
+                let length = (self.buffer.len() - length_at - 4) as i32;
+                self.buffer[length_at..length_at + 4].copy_from_slice(&length.to_be_bytes());
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buffer.extend_from_slice(&(-1i16).to_be_bytes());
+        self.buffer
+    }
+}
+
+impl Default for BinaryCopy {
+    fn default() -> Self {
+        let mut buffer = Vec::from(Self::SIGNATURE);
+        // flags, then the length of the header extension
+        buffer.extend_from_slice(&0i32.to_be_bytes());
+        buffer.extend_from_slice(&0i32.to_be_bytes());
+
+        Self { buffer }
+    }
+}
+
+impl CopyField for i64 {
+    fn write(&self, buffer: &mut Vec<u8>) -> bool {
+        buffer.extend_from_slice(&self.to_be_bytes());
+        true
+    }
+}
+
+impl CopyField for f64 {
+    fn write(&self, buffer: &mut Vec<u8>) -> bool {
+        buffer.extend_from_slice(&self.to_be_bytes());
+        true
+    }
+}
+
+impl CopyField for bool {
+    fn write(&self, buffer: &mut Vec<u8>) -> bool {
+        buffer.push(u8::from(*self));
+        true
+    }
+}
+
+impl CopyField for String {
+    fn write(&self, buffer: &mut Vec<u8>) -> bool {
+        buffer.extend_from_slice(self.as_bytes());
+        true
+    }
+}
+
+impl<T: CopyField> CopyField for Option<T> {
+    fn write(&self, buffer: &mut Vec<u8>) -> bool {
+        self.as_ref().is_some_and(|field| field.write(buffer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::copy::BinaryCopy;
+
+    #[test]
+    fn empty_copy() {
+        assert_eq!(
+            BinaryCopy::default().finish(),
+            b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0\xff\xff"
+        );
+    }
+
+    #[test]
+    fn encodes_rows() {
+        let mut copy = BinaryCopy::default();
+        copy.row(&[&7i64, &None::<String>, &true]);
+        copy.row(&[&1.5f64, &Some("ab".to_string())]);
+
+        let rows = &copy.finish()[19..];
+
+        assert_eq!(
+            rows,
+            [
+                &[0, 3][..],
+                &[0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 7],
+                &[0xff, 0xff, 0xff, 0xff],
+                &[0, 0, 0, 1, 1],
+                &[0, 2],
+                &[0, 0, 0, 8],
+                &1.5f64.to_be_bytes(),
+                &[0, 0, 0, 2, b'a', b'b'],
+                &[0xff, 0xff],
+            ]
+            .concat()
+        );
+    }
+}
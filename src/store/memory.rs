@@ -0,0 +1,417 @@
+use crate::{
+    area::SearchArea,
+    jobs::{IngestJob, JobStatus, Source, Stage},
+    osm::{WayEdge, WayTags},
+    segment::EdgeProfile,
+    store::GraphStore,
+    turns::TurnRestriction,
+};
+use anyhow::{anyhow, Result};
+use geo::{Contains, Coord, Distance, Haversine, Rect};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+/// Keeps the graph in memory, behaving like the Postgres schema.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    graph: Mutex<MemoryGraph>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryGraph {
+    nodes: HashMap<i64, MemoryNode>,
+    edges: HashMap<(i64, i64), WayEdge>,
+    /// Every way an edge belongs to, with whether it's oneway along that way.
+    edge_ways: HashMap<(i64, i64), BTreeMap<i64, bool>>,
+    profiles: HashMap<(i64, i64), EdgeProfile>,
+    ways: HashMap<i64, WayTags>,
+    restrictions: HashSet<TurnRestriction>,
+    jobs: Vec<IngestJob>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryNode {
+    coord: Option<Coord>,
+    elevation: Option<f64>,
+}
+
+impl MemoryGraph {
+    /// Sets the coordinate of existing nodes, clearing the elevation of nodes that moved
+    /// and the profile of their edges.
+    fn set_coordinates(&mut self, coords: HashMap<i64, Coord>) -> u64 {
+        let mut updated = 0;
+        let mut moved_node_ids = HashSet::new();
+
+        for (node_id, coord) in coords {
+            if let Some(node) = self.nodes.get_mut(&node_id) {
+                // elevations are kept while nodes stay within OSM's precision of their coordinate
+                let moved = node.coord.is_none_or(|previous| {
+                    (previous.x - coord.x).abs() >= 5e-8 || (previous.y - coord.y).abs() >= 5e-8
+                });
+
+                if moved {
+                    node.elevation = None;
+                    moved_node_ids.insert(node_id);
+                }
+
+                node.coord = Some(coord);
+                updated += 1;
+            }
+        }
+
+        self.profiles.retain(|(source, target), _| {
+            !moved_node_ids.contains(source) && !moved_node_ids.contains(target)
+        });
+
+        updated
+    }
+}
+
+impl MemoryStore {
+    fn graph(&self) -> Result<std::sync::MutexGuard<'_, MemoryGraph>> {
+        self.graph
+            .lock()
+            .map_err(|_| anyhow!("Expected the memory store to not be poisoned"))
+    }
+}
+
+impl GraphStore for MemoryStore {
+    async fn insert_node_ids(&self, node_ids: Vec<i64>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let mut inserted = 0;
+
+        for node_id in node_ids {
+            if let Entry::Vacant(entry) = graph.nodes.entry(node_id) {
+                entry.insert(MemoryNode::default());
+                inserted += 1;
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    async fn insert_nodes(&self, coords: HashMap<i64, Coord>) -> Result<u64> {
+        let mut graph = self.graph()?;
+
+        for node_id in coords.keys() {
+            graph.nodes.entry(*node_id).or_default();
+        }
+
+        Ok(graph.set_coordinates(coords))
+    }
+
+    async fn insert_way_tags(&self, ways: HashMap<i64, WayTags>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let inserted = ways.len() as u64;
+
+        graph.ways.extend(ways);
+
+        Ok(inserted)
+    }
+
+    async fn insert_edges(&self, edges: Vec<(i64, i64, WayEdge)>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let mut inserted = 0;
+
+        for (source, target, edge) in edges {
+            if !graph.nodes.contains_key(&source) || !graph.nodes.contains_key(&target) {
+                return Err(anyhow!(
+                    "Expected to find nodes {} and {} for the edge",
+                    source,
+                    target
+                ));
+            }
+
+            if let Some(way_id) = edge
+                .way_id
+                .filter(|way_id| !graph.ways.contains_key(way_id))
+            {
+                return Err(anyhow!("Expected to find way {} for the edge", way_id));
+            }
+
+            graph
+                .edges
+                .entry((source, target))
+                .and_modify(|existing| {
+                    existing.oneway &= edge.oneway;
+                    existing.way_id = existing.way_id.or(edge.way_id);
+                })
+                .or_insert(edge);
+
+            if let Some(way_id) = edge.way_id {
+                graph
+                    .edge_ways
+                    .entry((source, target))
+                    .or_default()
+                    .insert(way_id, edge.oneway);
+            }
+
+            inserted += 1;
+        }
+
+        Ok(inserted)
+    }
+
+    async fn insert_turn_restrictions(&self, restrictions: Vec<TurnRestriction>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let before = graph.restrictions.len();
+
+        graph.restrictions.extend(restrictions);
+
+        Ok((graph.restrictions.len() - before) as u64)
+    }
+
+    async fn query_node_ids(&self) -> Result<HashSet<i64>> {
+        let graph = self.graph()?;
+
+        Ok(graph
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.coord.is_none())
+            .map(|(node_id, _)| *node_id)
+            .collect())
+    }
+
+    async fn update_coordinates(&self, coords: HashMap<i64, Coord>) -> Result<u64> {
+        let mut graph = self.graph()?;
+
+        Ok(graph.set_coordinates(coords))
+    }
+
+    async fn delete_ways(&self, way_ids: Vec<i64>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let way_ids: HashSet<i64> = way_ids.into_iter().collect();
+
+        let MemoryGraph {
+            edges,
+            edge_ways,
+            profiles,
+            ..
+        } = &mut *graph;
+
+        for (key, ways) in edge_ways.iter_mut() {
+            let before = ways.len();
+            ways.retain(|way_id, _| !way_ids.contains(way_id));
+
+            // edges other ways still belong to take their way and direction from those
+            if ways.len() < before {
+                if let (Some(edge), Some((way_id, _))) =
+                    (edges.get_mut(key), ways.first_key_value())
+                {
+                    edge.way_id = Some(*way_id);
+                    edge.oneway = ways.values().all(|oneway| *oneway);
+                }
+            }
+        }
+
+        edge_ways.retain(|_, ways| !ways.is_empty());
+        edges.retain(|key, edge| {
+            edge.way_id.is_none_or(|way_id| !way_ids.contains(&way_id))
+                || edge_ways.contains_key(key)
+        });
+        profiles.retain(|key, _| edges.contains_key(key));
+
+        let before = graph.ways.len();
+        graph.ways.retain(|way_id, _| !way_ids.contains(way_id));
+
+        Ok((before - graph.ways.len()) as u64)
+    }
+
+    async fn delete_nodes(&self, node_ids: Vec<i64>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let node_ids: HashSet<i64> = node_ids.into_iter().collect();
+
+        graph
+            .edges
+            .retain(|(source, target), _| !node_ids.contains(source) && !node_ids.contains(target));
+
+        graph
+            .edge_ways
+            .retain(|(source, target), _| !node_ids.contains(source) && !node_ids.contains(target));
+
+        graph
+            .profiles
+            .retain(|(source, target), _| !node_ids.contains(source) && !node_ids.contains(target));
+
+        let before = graph.nodes.len();
+        graph.nodes.retain(|node_id, _| !node_ids.contains(node_id));
+
+        Ok((before - graph.nodes.len()) as u64)
+    }
+
+    async fn delete_orphaned_nodes(&self) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let edged: HashSet<i64> = graph
+            .edges
+            .keys()
+            .flat_map(|(source, target)| [*source, *target])
+            .collect();
+
+        let before = graph.nodes.len();
+        graph.nodes.retain(|node_id, _| edged.contains(node_id));
+
+        Ok((before - graph.nodes.len()) as u64)
+    }
+
+    async fn query_containing_coords(&self, rect: Rect) -> Result<HashMap<i64, Coord>> {
+        let graph = self.graph()?;
+
+        Ok(graph
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.elevation.is_none())
+            .filter_map(|(node_id, node)| node.coord.map(|coord| (*node_id, coord)))
+            .filter(|(_, coord)| rect.contains(coord))
+            .collect())
+    }
+
+    async fn update_elevations(&self, elevations: Vec<(i64, f64)>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let mut updated = 0;
+
+        for (node_id, elevation) in elevations {
+            if let Some(node) = graph.nodes.get_mut(&node_id) {
+                node.elevation = Some(elevation);
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    async fn query_unprofiled_edges(&self, rect: Rect) -> Result<Vec<(i64, i64, Coord, Coord)>> {
+        let graph = self.graph()?;
+        let coord = |node_id| {
+            graph
+                .nodes
+                .get(node_id)
+                .and_then(|node: &MemoryNode| node.coord)
+        };
+
+        Ok(graph
+            .edges
+            .keys()
+            .filter(|key| !graph.profiles.contains_key(key))
+            .filter_map(|(source, target)| Some((*source, *target, coord(source)?, coord(target)?)))
+            .filter(|(_, _, source, target)| rect.contains(source) || rect.contains(target))
+            .collect())
+    }
+
+    async fn update_edge_profiles(&self, profiles: Vec<(i64, i64, EdgeProfile)>) -> Result<u64> {
+        let mut graph = self.graph()?;
+        let mut updated = 0;
+
+        for (source, target, profile) in profiles {
+            if graph.edges.contains_key(&(source, target)) {
+                graph.profiles.insert((source, target), profile);
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    async fn query_nodes_within(&self, area: &SearchArea) -> Result<IndexMap<i64, (Coord, f64)>> {
+        let graph = self.graph()?;
+
+        Ok(graph
+            .nodes
+            .iter()
+            .filter_map(|(node_id, node)| Some((*node_id, (node.coord?, node.elevation?))))
+            .filter(|(_, (coord, _))| {
+                Haversine::distance(area.centre.into(), (*coord).into()) <= area.radius
+            })
+            .sorted_by(|(_, (_, a)), (_, (_, b))| b.total_cmp(a))
+            .collect())
+    }
+
+    async fn query_edges_between(&self, node_ids: &[i64]) -> Result<Vec<(i64, i64, WayEdge)>> {
+        let graph = self.graph()?;
+        let node_ids: HashSet<&i64> = node_ids.iter().collect();
+
+        Ok(graph
+            .edges
+            .iter()
+            .filter(|((source, target), _)| node_ids.contains(source) && node_ids.contains(target))
+            .map(|((source, target), edge)| (*source, *target, *edge))
+            .collect())
+    }
+
+    async fn query_edge_profiles(
+        &self,
+        node_ids: &[i64],
+    ) -> Result<HashMap<(i64, i64), EdgeProfile>> {
+        let graph = self.graph()?;
+        let node_ids: HashSet<&i64> = node_ids.iter().collect();
+
+        Ok(graph
+            .profiles
+            .iter()
+            .filter(|((source, target), _)| node_ids.contains(source) && node_ids.contains(target))
+            .map(|(key, profile)| (*key, *profile))
+            .collect())
+    }
+
+    async fn query_way_tags(&self, way_ids: &[i64]) -> Result<HashMap<i64, WayTags>> {
+        let graph = self.graph()?;
+
+        Ok(way_ids
+            .iter()
+            .filter_map(|way_id| Some((*way_id, graph.ways.get(way_id)?.clone())))
+            .collect())
+    }
+
+    async fn query_turn_restrictions(&self, node_ids: &[i64]) -> Result<Vec<TurnRestriction>> {
+        let graph = self.graph()?;
+        let node_ids: HashSet<&i64> = node_ids.iter().collect();
+
+        Ok(graph
+            .restrictions
+            .iter()
+            .filter(|restriction| node_ids.contains(&restriction.via_node_id))
+            .copied()
+            .collect())
+    }
+
+    async fn query_jobs(&self) -> Result<Vec<IngestJob>> {
+        Ok(self.graph()?.jobs.clone())
+    }
+
+    async fn insert_job(&self, stage: Stage, source: &Source) -> Result<i64> {
+        let mut graph = self.graph()?;
+        let job_id = graph.jobs.len() as i64 + 1;
+
+        graph.jobs.push(IngestJob {
+            id: job_id,
+            stage,
+            source_path: source.path.clone(),
+            source_hash: source.hash.clone(),
+            status: JobStatus::Running,
+        });
+
+        Ok(job_id)
+    }
+
+    async fn update_job_status(
+        &self,
+        job_id: i64,
+        status: JobStatus,
+        _error: Option<String>,
+    ) -> Result<()> {
+        let mut graph = self.graph()?;
+
+        let job = graph
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == job_id)
+            .ok_or_else(|| anyhow!("Expected to find job {}", job_id))?;
+
+        job.status = status;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,818 @@
+use crate::{
+    area::SearchArea,
+    jobs::{IngestJob, JobStatus, Source, Stage},
+    osm::{WayEdge, WayTags},
+    segment::EdgeProfile,
+    store::{copy::BinaryCopy, GraphStore},
+    turns::TurnRestriction,
+};
+use anyhow::Result;
+use geo::{Coord, Rect};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use log::info;
+use sqlx::{PgPool, Row};
+use std::collections::{HashMap, HashSet};
+
+/// Rows sent in each `COPY`, so a large region is never encoded or sent all at once.
+const COPY_BATCH_SIZE: usize = 100_000;
+
+/// Clears the profile of edges touching nodes in `staging_osm_node_coord` that moved,
+/// for the merges that set coordinates.
+///
+/// Nodes are considered to stay put while within OSM's precision of their coordinate.
+const UNPROFILE_MOVED_NODES: &str = r#"
+    WITH moved AS (
+        SELECT t.id FROM osm_node AS t
+        JOIN staging_osm_node_coord AS params ON t.id = params.id
+        WHERE t.coord IS NULL
+        OR abs(ST_X(t.coord) - params.lon) >= 5e-8
+        OR abs(ST_Y(t.coord) - params.lat) >= 5e-8
+    ), unprofiled AS (
+        UPDATE osm_node_edge
+        SET climbing = NULL, descending = NULL, max_gradient = NULL, min_gradient = NULL
+        WHERE source_node_id IN (SELECT id FROM moved)
+        OR target_node_id IN (SELECT id FROM moved)
+    )
+"#;
+
+pub struct PgStore {
+    pool: PgPool,
+}
+
+/// A temporary table that rows are copied into before being merged into the schema.
+struct Staging<'a> {
+    table: &'a str,
+    /// Column definitions, in the order the fields of each row are written.
+    columns: &'a str,
+    /// Set-based statement that merges the staged rows into the schema.
+    merge: &'a str,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Copies rows into a staging table in batches with binary `COPY`, then merges
+    /// them in one statement, all within a transaction.
+    async fn copy_merge<T>(
+        &self,
+        staging: Staging<'_>,
+        rows: Vec<T>,
+        write_row: impl Fn(&mut BinaryCopy, &T),
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let create = format!(
+            "CREATE TEMPORARY TABLE {} ({}) ON COMMIT DROP",
+            staging.table, staging.columns
+        );
+        sqlx::query(&create).execute(&mut *tx).await?;
+
+        let statement = format!("COPY {} FROM STDIN (FORMAT binary)", staging.table);
+        let batches = rows.len().div_ceil(COPY_BATCH_SIZE);
+        let mut copied = 0;
+
+        for (batch, chunk) in rows.chunks(COPY_BATCH_SIZE).enumerate() {
+            let mut copy = BinaryCopy::default();
+
+            for row in chunk {
+                write_row(&mut copy, row);
+            }
+
+            let mut copy_in = tx.copy_in_raw(&statement).await?;
+            copy_in.send(copy.finish()).await?;
+            copied += copy_in.finish().await?;
+
+            info!(
+                "Copied batch {}/{} into {}, {}/{} rows",
+                batch + 1,
+                batches,
+                staging.table,
+                copied,
+                rows.len()
+            );
+        }
+
+        let merged = sqlx::query(staging.merge)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(merged)
+    }
+}
+
+impl GraphStore for PgStore {
+    async fn insert_node_ids(&self, node_ids: Vec<i64>) -> Result<u64> {
+        info!("Inserting nodes");
+
+        let staging = Staging {
+            table: "staging_osm_node",
+            columns: "id BIGINT",
+            merge: r#"
+                INSERT INTO osm_node(id)
+                SELECT id FROM staging_osm_node
+                ON CONFLICT DO NOTHING
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(staging, node_ids, |copy, node_id| copy.row(&[node_id]))
+            .await?;
+
+        info!("Inserted {} nodes", updated);
+
+        Ok(updated)
+    }
+
+    async fn insert_nodes(&self, coords: HashMap<i64, Coord>) -> Result<u64> {
+        info!("Inserting {} nodes with coords", coords.len());
+
+        // existing nodes that moved lose their elevation, as in `update_coordinates`
+        let merge = format!(
+            r#"
+                {}
+                INSERT INTO osm_node(id, coord)
+                SELECT id, ST_SetSRID(ST_Point(lon, lat), 4326) FROM staging_osm_node_coord
+                ON CONFLICT (id) DO UPDATE SET
+                    coord = EXCLUDED.coord,
+                    elevation = CASE
+                        WHEN abs(ST_X(osm_node.coord) - ST_X(EXCLUDED.coord)) < 5e-8
+                        AND abs(ST_Y(osm_node.coord) - ST_Y(EXCLUDED.coord)) < 5e-8
+                        THEN osm_node.elevation
+                    END
+            "#,
+            UNPROFILE_MOVED_NODES
+        );
+
+        let staging = Staging {
+            table: "staging_osm_node_coord",
+            columns: "id BIGINT, lon DOUBLE PRECISION, lat DOUBLE PRECISION",
+            merge: &merge,
+        };
+
+        let updated = self
+            .copy_merge(
+                staging,
+                coords.into_iter().collect(),
+                |copy, (node_id, coord)| copy.row(&[node_id, &coord.x, &coord.y]),
+            )
+            .await?;
+
+        info!("Inserted {} nodes with coords", updated);
+
+        Ok(updated)
+    }
+
+    async fn insert_way_tags(&self, ways: HashMap<i64, WayTags>) -> Result<u64> {
+        info!("Inserting ways");
+
+        let staging = Staging {
+            table: "staging_osm_way",
+            columns: r#"
+                id BIGINT, highway TEXT, name TEXT, surface TEXT, smoothness TEXT,
+                tracktype TEXT, maxspeed TEXT, lit TEXT, access TEXT, bicycle TEXT
+            "#,
+            merge: r#"
+                INSERT INTO osm_way(id,highway,name,surface,smoothness,tracktype,maxspeed,lit,access,bicycle)
+                SELECT * FROM staging_osm_way
+                ON CONFLICT (id) DO UPDATE SET
+                    highway = EXCLUDED.highway,
+                    name = EXCLUDED.name,
+                    surface = EXCLUDED.surface,
+                    smoothness = EXCLUDED.smoothness,
+                    tracktype = EXCLUDED.tracktype,
+                    maxspeed = EXCLUDED.maxspeed,
+                    lit = EXCLUDED.lit,
+                    access = EXCLUDED.access,
+                    bicycle = EXCLUDED.bicycle
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(
+                staging,
+                ways.into_iter().collect(),
+                |copy, (way_id, tags)| {
+                    copy.row(&[
+                        way_id,
+                        &tags.highway,
+                        &tags.name,
+                        &tags.surface,
+                        &tags.smoothness,
+                        &tags.tracktype,
+                        &tags.maxspeed,
+                        &tags.lit,
+                        &tags.access,
+                        &tags.bicycle,
+                    ])
+                },
+            )
+            .await?;
+
+        info!("Inserted {} ways", updated);
+
+        Ok(updated)
+    }
+
+    async fn insert_edges(&self, edges: Vec<(i64, i64, WayEdge)>) -> Result<u64> {
+        info!("Inserting edges");
+
+        let staging = Staging {
+            table: "staging_osm_node_edge",
+            columns: "source_node_id BIGINT, target_node_id BIGINT, oneway BOOLEAN, way_id BIGINT",
+            // ways sharing a pair of nodes are staged as copies of the same edge
+            merge: r#"
+                WITH edges AS (
+                    INSERT INTO osm_node_edge(source_node_id,target_node_id,oneway,way_id)
+                    SELECT source_node_id, target_node_id, bool_and(oneway), min(way_id)
+                    FROM staging_osm_node_edge
+                    GROUP BY source_node_id, target_node_id
+                    ON CONFLICT (source_node_id, target_node_id) DO UPDATE
+                    SET oneway = osm_node_edge.oneway AND EXCLUDED.oneway,
+                        way_id = COALESCE(osm_node_edge.way_id, EXCLUDED.way_id)
+                )
+                INSERT INTO osm_node_edge_way(source_node_id,target_node_id,way_id,oneway)
+                SELECT DISTINCT ON (source_node_id, target_node_id, way_id)
+                    source_node_id, target_node_id, way_id, oneway
+                FROM staging_osm_node_edge
+                WHERE way_id IS NOT NULL
+                ON CONFLICT (source_node_id, target_node_id, way_id) DO UPDATE
+                SET oneway = EXCLUDED.oneway
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(staging, edges, |copy, (source, target, edge)| {
+                copy.row(&[source, target, &edge.oneway, &edge.way_id])
+            })
+            .await?;
+
+        info!("Inserted {} edges", updated);
+
+        Ok(updated)
+    }
+
+    async fn insert_turn_restrictions(&self, restrictions: Vec<TurnRestriction>) -> Result<u64> {
+        info!("Inserting turn restrictions");
+
+        let staging = Staging {
+            table: "staging_osm_turn_restriction",
+            columns: r#"
+                relation_id BIGINT, from_way_id BIGINT, via_node_id BIGINT,
+                to_way_id BIGINT, only BOOLEAN
+            "#,
+            merge: r#"
+                INSERT INTO osm_turn_restriction(relation_id,from_way_id,via_node_id,to_way_id,only)
+                SELECT * FROM staging_osm_turn_restriction
+                ON CONFLICT DO NOTHING
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(staging, restrictions, |copy, restriction| {
+                copy.row(&[
+                    &restriction.relation_id,
+                    &restriction.from_way_id,
+                    &restriction.via_node_id,
+                    &restriction.to_way_id,
+                    &restriction.only,
+                ])
+            })
+            .await?;
+
+        info!("Inserted {} turn restrictions", updated);
+
+        Ok(updated)
+    }
+
+    async fn query_node_ids(&self) -> Result<HashSet<i64>> {
+        info!("Querying cyclable nodes");
+        let cycleable_node_ids: HashSet<i64> =
+            sqlx::query(r#"SELECT id FROM osm_node WHERE coord IS NULL"#)
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| row.try_get::<i64, &str>("id"))
+                .try_collect()?;
+
+        info!("Queried {} cyclable nodes", cycleable_node_ids.len());
+
+        Ok(cycleable_node_ids)
+    }
+
+    async fn update_coordinates(&self, coords: HashMap<i64, Coord>) -> Result<u64> {
+        info!("Inserting {} coords", coords.len());
+
+        // elevations are kept while nodes stay within OSM's precision of their coordinate
+        let merge = format!(
+            r#"
+                {}
+                UPDATE osm_node AS t
+                SET coord = ST_SetSRID(ST_Point(params.lon, params.lat), 4326),
+                    elevation = CASE
+                        WHEN abs(ST_X(t.coord) - params.lon) < 5e-8
+                        AND abs(ST_Y(t.coord) - params.lat) < 5e-8
+                        THEN t.elevation
+                    END
+                FROM staging_osm_node_coord AS params
+                WHERE t.id = params.id
+            "#,
+            UNPROFILE_MOVED_NODES
+        );
+
+        let staging = Staging {
+            table: "staging_osm_node_coord",
+            columns: "id BIGINT, lon DOUBLE PRECISION, lat DOUBLE PRECISION",
+            merge: &merge,
+        };
+
+        let updated = self
+            .copy_merge(
+                staging,
+                coords.into_iter().collect(),
+                |copy, (node_id, coord)| copy.row(&[node_id, &coord.x, &coord.y]),
+            )
+            .await?;
+
+        info!("Inserted {} coordinates", updated);
+
+        Ok(updated)
+    }
+
+    async fn delete_ways(&self, way_ids: Vec<i64>) -> Result<u64> {
+        info!("Deleting {} ways", way_ids.len());
+
+        let mut tx = self.pool.begin().await?;
+
+        // edges other ways still belong to take their way and direction from those
+        let query = r#"
+            WITH deleted AS (
+                DELETE FROM osm_node_edge_way
+                WHERE way_id = ANY($1::bigint[])
+                RETURNING source_node_id, target_node_id
+            ), remaining AS (
+                SELECT source_node_id, target_node_id, bool_and(oneway) AS oneway, min(way_id) AS way_id
+                FROM osm_node_edge_way
+                WHERE (source_node_id, target_node_id) IN (SELECT * FROM deleted)
+                AND NOT way_id = ANY($1::bigint[])
+                GROUP BY source_node_id, target_node_id
+            )
+            UPDATE osm_node_edge AS t
+            SET oneway = remaining.oneway, way_id = remaining.way_id
+            FROM remaining
+            WHERE t.source_node_id = remaining.source_node_id
+            AND t.target_node_id = remaining.target_node_id
+        "#;
+
+        sqlx::query(query).bind(&way_ids).execute(&mut *tx).await?;
+
+        let query = r#"
+            DELETE FROM osm_node_edge AS t
+            WHERE way_id = ANY($1::bigint[])
+            AND NOT EXISTS (
+                SELECT 1 FROM osm_node_edge_way AS w
+                WHERE w.source_node_id = t.source_node_id AND w.target_node_id = t.target_node_id
+            )
+        "#;
+
+        sqlx::query(query).bind(&way_ids).execute(&mut *tx).await?;
+
+        let deleted = sqlx::query("DELETE FROM osm_way WHERE id = ANY($1::bigint[])")
+            .bind(&way_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        info!("Deleted {} ways", deleted);
+
+        Ok(deleted)
+    }
+
+    async fn delete_nodes(&self, node_ids: Vec<i64>) -> Result<u64> {
+        info!("Deleting {} nodes", node_ids.len());
+
+        let mut tx = self.pool.begin().await?;
+
+        let query = r#"
+            DELETE FROM osm_node_edge
+            WHERE source_node_id = ANY($1::bigint[]) OR target_node_id = ANY($1::bigint[])
+        "#;
+
+        sqlx::query(query).bind(&node_ids).execute(&mut *tx).await?;
+
+        let deleted = sqlx::query("DELETE FROM osm_node WHERE id = ANY($1::bigint[])")
+            .bind(&node_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        info!("Deleted {} nodes", deleted);
+
+        Ok(deleted)
+    }
+
+    async fn delete_orphaned_nodes(&self) -> Result<u64> {
+        info!("Deleting orphaned nodes");
+
+        let query = r#"
+            DELETE FROM osm_node AS t
+            WHERE NOT EXISTS (SELECT 1 FROM osm_node_edge WHERE source_node_id = t.id)
+            AND NOT EXISTS (SELECT 1 FROM osm_node_edge WHERE target_node_id = t.id)
+        "#;
+
+        let deleted = sqlx::query(query)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        info!("Deleted {} orphaned nodes", deleted);
+
+        Ok(deleted)
+    }
+
+    async fn query_containing_coords(&self, rect: Rect) -> Result<HashMap<i64, Coord>> {
+        info!("Querying containing coords");
+        let query = r#"
+            SELECT id, ST_X(coord) as x, ST_Y(coord) as Y FROM osm_node
+            WHERE elevation IS NULL AND coord IS NOT NULL
+            AND ST_Within(coord, ST_MakeEnvelope($1, $2, $3, $4, 4326))
+        "#;
+
+        let min = rect.min();
+        let max = rect.max();
+        let rows: HashMap<i64, Coord> = sqlx::query(query)
+            .bind(min.x)
+            .bind(min.y)
+            .bind(max.x)
+            .bind(max.y)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<(i64, Coord)> {
+                let id: i64 = row.try_get("id")?;
+                let x: f64 = row.try_get("x")?;
+                let y: f64 = row.try_get("y")?;
+                let coord = Coord { x, y };
+                Ok((id, coord))
+            })
+            .try_collect()?;
+
+        let size = rows.len();
+        info!("Queried {} containing coords", size);
+
+        Ok(rows)
+    }
+
+    async fn update_elevations(&self, elevations: Vec<(i64, f64)>) -> Result<u64> {
+        info!("Updating elevations");
+
+        let staging = Staging {
+            table: "staging_osm_node_elevation",
+            columns: "id BIGINT, elevation DOUBLE PRECISION",
+            merge: r#"
+                UPDATE osm_node AS t
+                SET elevation = params.elevation
+                FROM staging_osm_node_elevation AS params
+                WHERE t.id = params.id
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(staging, elevations, |copy, (node_id, elevation)| {
+                copy.row(&[node_id, elevation])
+            })
+            .await?;
+
+        info!("Updated {} elevations", updated);
+
+        Ok(updated)
+    }
+
+    async fn query_unprofiled_edges(&self, rect: Rect) -> Result<Vec<(i64, i64, Coord, Coord)>> {
+        info!("Querying unprofiled edges");
+        let query = r#"
+            SELECT
+                source_node_id,
+                target_node_id,
+                ST_X(source.coord) AS source_x,
+                ST_Y(source.coord) AS source_y,
+                ST_X(target.coord) AS target_x,
+                ST_Y(target.coord) AS target_y
+            FROM osm_node_edge
+            JOIN osm_node AS source ON source.id = source_node_id
+            JOIN osm_node AS target ON target.id = target_node_id
+            WHERE climbing IS NULL
+            AND (
+                ST_Within(source.coord, ST_MakeEnvelope($1, $2, $3, $4, 4326))
+                OR ST_Within(target.coord, ST_MakeEnvelope($1, $2, $3, $4, 4326))
+            )
+        "#;
+
+        let min = rect.min();
+        let max = rect.max();
+        let edges: Vec<(i64, i64, Coord, Coord)> = sqlx::query(query)
+            .bind(min.x)
+            .bind(min.y)
+            .bind(max.x)
+            .bind(max.y)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<_> {
+                let source = Coord {
+                    x: row.try_get("source_x")?,
+                    y: row.try_get("source_y")?,
+                };
+                let target = Coord {
+                    x: row.try_get("target_x")?,
+                    y: row.try_get("target_y")?,
+                };
+                Ok((
+                    row.try_get("source_node_id")?,
+                    row.try_get("target_node_id")?,
+                    source,
+                    target,
+                ))
+            })
+            .try_collect()?;
+
+        info!("Queried {} unprofiled edges", edges.len());
+
+        Ok(edges)
+    }
+
+    async fn update_edge_profiles(&self, profiles: Vec<(i64, i64, EdgeProfile)>) -> Result<u64> {
+        info!("Updating edge profiles");
+
+        let staging = Staging {
+            table: "staging_osm_node_edge_profile",
+            columns: r#"
+                source_node_id BIGINT,
+                target_node_id BIGINT,
+                climbing DOUBLE PRECISION,
+                descending DOUBLE PRECISION,
+                max_gradient DOUBLE PRECISION,
+                min_gradient DOUBLE PRECISION
+            "#,
+            merge: r#"
+                UPDATE osm_node_edge AS t
+                SET climbing = params.climbing,
+                    descending = params.descending,
+                    max_gradient = params.max_gradient,
+                    min_gradient = params.min_gradient
+                FROM staging_osm_node_edge_profile AS params
+                WHERE t.source_node_id = params.source_node_id
+                AND t.target_node_id = params.target_node_id
+            "#,
+        };
+
+        let updated = self
+            .copy_merge(staging, profiles, |copy, (source, target, profile)| {
+                copy.row(&[
+                    source,
+                    target,
+                    &profile.climbing,
+                    &profile.descending,
+                    &profile.max_gradient,
+                    &profile.min_gradient,
+                ])
+            })
+            .await?;
+
+        info!("Updated {} edge profiles", updated);
+
+        Ok(updated)
+    }
+
+    async fn query_nodes_within(&self, area: &SearchArea) -> Result<IndexMap<i64, (Coord, f64)>> {
+        let envelope = area.bounding_rect();
+
+        // the envelope narrows the rows before geography measures metres on a sphere.
+        let query = r#"
+            SELECT
+                id,
+                ST_X(coord) as x,
+                ST_Y(coord) as y,
+                elevation FROM osm_node
+            WHERE coord && ST_MakeEnvelope($4, $5, $6, $7, 4326)
+            AND ST_DWithin(
+                coord::geography,
+                ST_SetSRID(ST_MakePoint($1, $2), 4326)::geography,
+                $3,
+                false
+            ) AND elevation IS NOT NULL
+            ORDER BY elevation DESC
+        "#;
+
+        let nodes = sqlx::query(query)
+            .bind(area.centre.x)
+            .bind(area.centre.y)
+            .bind(area.radius)
+            .bind(envelope.min().x)
+            .bind(envelope.min().y)
+            .bind(envelope.max().x)
+            .bind(envelope.max().y)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<_> {
+                let node_id: i64 = row.try_get("id")?;
+                let x: f64 = row.try_get("x")?;
+                let y: f64 = row.try_get("y")?;
+                let coord = Coord { x, y };
+                let elevation: f64 = row.try_get("elevation")?;
+                Ok((node_id, (coord, elevation)))
+            })
+            .try_collect()?;
+
+        Ok(nodes)
+    }
+
+    async fn query_edges_between(&self, node_ids: &[i64]) -> Result<Vec<(i64, i64, WayEdge)>> {
+        let query = r#"
+            SELECT source_node_id, target_node_id, oneway, way_id FROM osm_node_edge
+            WHERE source_node_id = ANY($1::bigint[]) AND target_node_id = ANY($1::bigint[])
+        "#;
+
+        let edges = sqlx::query(query)
+            .bind(node_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<(i64, i64, WayEdge)> {
+                let source: i64 = row.try_get("source_node_id")?;
+                let target: i64 = row.try_get("target_node_id")?;
+                let oneway: bool = row.try_get("oneway")?;
+                let way_id: Option<i64> = row.try_get("way_id")?;
+                Ok((source, target, WayEdge { oneway, way_id }))
+            })
+            .try_collect()?;
+
+        Ok(edges)
+    }
+
+    async fn query_edge_profiles(
+        &self,
+        node_ids: &[i64],
+    ) -> Result<HashMap<(i64, i64), EdgeProfile>> {
+        let query = r#"
+            SELECT source_node_id, target_node_id, climbing, descending, max_gradient, min_gradient
+            FROM osm_node_edge
+            WHERE source_node_id = ANY($1::bigint[]) AND target_node_id = ANY($1::bigint[])
+            AND climbing IS NOT NULL
+        "#;
+
+        let profiles = sqlx::query(query)
+            .bind(node_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<((i64, i64), EdgeProfile)> {
+                let source: i64 = row.try_get("source_node_id")?;
+                let target: i64 = row.try_get("target_node_id")?;
+                let profile = EdgeProfile {
+                    climbing: row.try_get("climbing")?,
+                    descending: row.try_get("descending")?,
+                    max_gradient: row.try_get("max_gradient")?,
+                    min_gradient: row.try_get("min_gradient")?,
+                };
+                Ok(((source, target), profile))
+            })
+            .try_collect()?;
+
+        Ok(profiles)
+    }
+
+    async fn query_way_tags(&self, way_ids: &[i64]) -> Result<HashMap<i64, WayTags>> {
+        let query = r#"
+            SELECT id, highway, name, surface, smoothness, tracktype, maxspeed, lit, access, bicycle
+            FROM osm_way
+            WHERE id = ANY($1::bigint[])
+        "#;
+
+        let ways = sqlx::query(query)
+            .bind(way_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<(i64, WayTags)> {
+                let tags = WayTags {
+                    highway: row.try_get("highway")?,
+                    name: row.try_get("name")?,
+                    surface: row.try_get("surface")?,
+                    smoothness: row.try_get("smoothness")?,
+                    tracktype: row.try_get("tracktype")?,
+                    maxspeed: row.try_get("maxspeed")?,
+                    lit: row.try_get("lit")?,
+                    access: row.try_get("access")?,
+                    bicycle: row.try_get("bicycle")?,
+                };
+
+                Ok((row.try_get("id")?, tags))
+            })
+            .try_collect()?;
+
+        Ok(ways)
+    }
+
+    async fn query_turn_restrictions(&self, node_ids: &[i64]) -> Result<Vec<TurnRestriction>> {
+        let query = r#"
+            SELECT relation_id, from_way_id, via_node_id, to_way_id, only
+            FROM osm_turn_restriction
+            WHERE via_node_id = ANY($1::bigint[])
+        "#;
+
+        let restrictions = sqlx::query(query)
+            .bind(node_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<TurnRestriction> {
+                Ok(TurnRestriction {
+                    relation_id: row.try_get("relation_id")?,
+                    from_way_id: row.try_get("from_way_id")?,
+                    via_node_id: row.try_get("via_node_id")?,
+                    to_way_id: row.try_get("to_way_id")?,
+                    only: row.try_get("only")?,
+                })
+            })
+            .try_collect()?;
+
+        Ok(restrictions)
+    }
+
+    async fn query_jobs(&self) -> Result<Vec<IngestJob>> {
+        let query = r#"
+            SELECT id, stage, source_path, source_hash, status
+            FROM ingest_job
+            ORDER BY id
+        "#;
+
+        let jobs = sqlx::query(query)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<IngestJob> {
+                Ok(IngestJob {
+                    id: row.try_get("id")?,
+                    stage: row.try_get::<&str, _>("stage")?.parse()?,
+                    source_path: row.try_get("source_path")?,
+                    source_hash: row.try_get("source_hash")?,
+                    status: row.try_get::<&str, _>("status")?.parse()?,
+                })
+            })
+            .try_collect()?;
+
+        Ok(jobs)
+    }
+
+    async fn insert_job(&self, stage: Stage, source: &Source) -> Result<i64> {
+        let query = r#"
+            INSERT INTO ingest_job(stage, source_path, source_hash, status)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        "#;
+
+        let job_id = sqlx::query(query)
+            .bind(stage.as_str())
+            .bind(&source.path)
+            .bind(&source.hash)
+            .bind(JobStatus::Running.as_str())
+            .fetch_one(&self.pool)
+            .await?
+            .try_get("id")?;
+
+        Ok(job_id)
+    }
+
+    async fn update_job_status(
+        &self,
+        job_id: i64,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<()> {
+        let query = r#"
+            UPDATE ingest_job
+            SET status = $2, error = $3, finished_at = now()
+            WHERE id = $1
+        "#;
+
+        sqlx::query(query)
+            .bind(job_id)
+            .bind(status.as_str())
+            .bind(error)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
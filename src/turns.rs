@@ -0,0 +1,283 @@
+use osmpbf::RelMemberType;
+use std::collections::{HashMap, HashSet};
+
+/// A turn from one way onto another at the node they share, restricted by a relation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TurnRestriction {
+    pub relation_id: i64,
+    pub from_way_id: i64,
+    pub via_node_id: i64,
+    pub to_way_id: i64,
+    /// The turn is the only one allowed from the way, rather than forbidden.
+    pub only: bool,
+}
+
+impl TurnRestriction {
+    /// Reads the restrictions a bicycle has to follow from a `type=restriction` relation.
+    ///
+    /// Restrictions with a way as the via member aren't supported and are skipped.
+    /// Inferred from https://wiki.openstreetmap.org/wiki/Relation:restriction
+    pub fn from_relation(
+        relation_id: i64,
+        tags: &[(&str, &str)],
+        members: &[(&str, RelMemberType, i64)],
+    ) -> Vec<Self> {
+        let value = |key: &str| {
+            tags.iter()
+                .find(|(tag_key, _)| *tag_key == key)
+                .map(|(_, value)| *value)
+        };
+
+        if value("type") != Some("restriction") {
+            return Vec::new();
+        }
+
+        let except_bicycle = value("except")
+            .is_some_and(|except| except.split(';').any(|mode| mode.trim() == "bicycle"));
+
+        if except_bicycle {
+            return Vec::new();
+        }
+
+        // restrictions for bicycles replace the restriction for everyone else
+        let only = match value("restriction:bicycle").or_else(|| value("restriction")) {
+            Some(restriction) if restriction.starts_with("only_") => true,
+            Some(restriction) if restriction.starts_with("no_") => false,
+            _ => return Vec::new(),
+        };
+
+        let ways = |role: &'static str| {
+            members
+                .iter()
+                .filter(move |(member_role, member_type, _)| {
+                    *member_role == role && *member_type == RelMemberType::Way
+                })
+                .map(|(_, _, way_id)| *way_id)
+        };
+
+        let vias = members
+            .iter()
+            .filter(|(role, _, _)| *role == "via")
+            .collect::<Vec<_>>();
+
+        let via_node_id = match vias.as_slice() {
+            [(_, RelMemberType::Node, node_id)] => *node_id,
+            _ => return Vec::new(),
+        };
+
+        ways("from")
+            .flat_map(|from_way_id| {
+                ways("to").map(move |to_way_id| TurnRestriction {
+                    relation_id,
+                    from_way_id,
+                    via_node_id,
+                    to_way_id,
+                    only,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Turn restrictions grouped by where they start, for checking turns while routing.
+#[derive(Debug, Clone, Default)]
+pub struct TurnRestrictions {
+    forbidden: HashSet<(i64, i64, i64)>,
+    only: HashMap<(i64, i64), HashSet<i64>>,
+}
+
+/// Riding from the previous node through the via node to the next one,
+/// along an edge of the `from` way and then an edge of the `to` way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turn {
+    pub from_way_id: i64,
+    pub previous_node_id: i64,
+    pub via_node_id: i64,
+    pub next_node_id: i64,
+    pub to_way_id: i64,
+}
+
+impl Turn {
+    /// Heads back to the node it came from.
+    fn is_u_turn(&self) -> bool {
+        self.previous_node_id == self.next_node_id
+    }
+
+    /// Carries on along the same way through the via node, which isn't a turn at all.
+    fn is_straight_through(&self) -> bool {
+        self.from_way_id == self.to_way_id && !self.is_u_turn()
+    }
+}
+
+impl TurnRestrictions {
+    /// Returns true when a bicycle may make a turn.
+    ///
+    /// The from and to ways of a restriction are only the same way for U-turns,
+    /// so carrying on along that way through the via node is never restricted.
+    pub fn permits(&self, turn: Turn) -> bool {
+        if turn.is_straight_through() {
+            return true;
+        }
+
+        if self
+            .forbidden
+            .contains(&(turn.from_way_id, turn.via_node_id, turn.to_way_id))
+        {
+            return false;
+        }
+
+        self.only
+            .get(&(turn.from_way_id, turn.via_node_id))
+            .is_none_or(|to_way_ids| to_way_ids.contains(&turn.to_way_id))
+    }
+}
+
+impl FromIterator<TurnRestriction> for TurnRestrictions {
+    fn from_iter<T: IntoIterator<Item = TurnRestriction>>(iter: T) -> Self {
+        let mut restrictions = Self::default();
+
+        for restriction in iter {
+            let from = (restriction.from_way_id, restriction.via_node_id);
+
+            if restriction.only {
+                restrictions
+                    .only
+                    .entry(from)
+                    .or_default()
+                    .insert(restriction.to_way_id);
+            } else {
+                restrictions
+                    .forbidden
+                    .insert((from.0, from.1, restriction.to_way_id));
+            }
+        }
+
+        restrictions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::turns::{Turn, TurnRestriction, TurnRestrictions};
+    use osmpbf::RelMemberType;
+
+    fn members() -> Vec<(&'static str, RelMemberType, i64)> {
+        vec![
+            ("from", RelMemberType::Way, 10),
+            ("via", RelMemberType::Node, 5),
+            ("to", RelMemberType::Way, 20),
+        ]
+    }
+
+    #[test]
+    fn reads_restrictions() {
+        let tags = [("type", "restriction"), ("restriction", "no_left_turn")];
+
+        assert_eq!(
+            TurnRestriction::from_relation(1, &tags, &members()),
+            vec![TurnRestriction {
+                relation_id: 1,
+                from_way_id: 10,
+                via_node_id: 5,
+                to_way_id: 20,
+                only: false,
+            }]
+        );
+
+        let tags = [("type", "restriction"), ("restriction", "only_straight_on")];
+        assert!(TurnRestriction::from_relation(1, &tags, &members())[0].only);
+    }
+
+    #[test]
+    fn bicycles_can_be_excepted() {
+        let tags = [
+            ("type", "restriction"),
+            ("restriction", "no_right_turn"),
+            ("except", "psv;bicycle"),
+        ];
+        assert!(TurnRestriction::from_relation(1, &tags, &members()).is_empty());
+
+        let tags = [
+            ("type", "restriction"),
+            ("restriction:hgv", "no_right_turn"),
+        ];
+        assert!(TurnRestriction::from_relation(1, &tags, &members()).is_empty());
+
+        let tags = [
+            ("type", "restriction"),
+            ("restriction", "no_right_turn"),
+            ("restriction:bicycle", "only_straight_on"),
+        ];
+        assert!(TurnRestriction::from_relation(1, &tags, &members())[0].only);
+    }
+
+    #[test]
+    fn skips_via_ways() {
+        let tags = [("type", "restriction"), ("restriction", "no_u_turn")];
+        let members = [
+            ("from", RelMemberType::Way, 10),
+            ("via", RelMemberType::Way, 15),
+            ("to", RelMemberType::Way, 10),
+        ];
+
+        assert!(TurnRestriction::from_relation(1, &tags, &members).is_empty());
+    }
+
+    fn restriction(from_way_id: i64, to_way_id: i64, only: bool) -> TurnRestriction {
+        TurnRestriction {
+            relation_id: 1,
+            from_way_id,
+            via_node_id: 5,
+            to_way_id,
+            only,
+        }
+    }
+
+    /// Riding from node 4 through `via_node_id` to `next_node_id`.
+    fn turn(from_way_id: i64, via_node_id: i64, next_node_id: i64, to_way_id: i64) -> Turn {
+        Turn {
+            from_way_id,
+            previous_node_id: 4,
+            via_node_id,
+            next_node_id,
+            to_way_id,
+        }
+    }
+
+    #[test]
+    fn permits_turns() {
+        let restrictions: TurnRestrictions = [
+            restriction(10, 20, false),
+            restriction(30, 40, true),
+            restriction(30, 50, true),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(!restrictions.permits(turn(10, 5, 6, 20)));
+        assert!(restrictions.permits(turn(10, 5, 6, 30)));
+        assert!(restrictions.permits(turn(10, 6, 7, 20)));
+        assert!(restrictions.permits(turn(30, 5, 6, 40)));
+        assert!(restrictions.permits(turn(30, 5, 6, 50)));
+        assert!(!restrictions.permits(turn(30, 5, 6, 20)));
+    }
+
+    #[test]
+    fn u_turns_are_not_straight_through() {
+        let restrictions: TurnRestrictions = [restriction(10, 10, false)].into_iter().collect();
+
+        assert!(!restrictions.permits(turn(10, 5, 4, 10)));
+        assert!(restrictions.permits(turn(10, 5, 6, 10)));
+    }
+
+    #[test]
+    fn only_turns_let_the_way_carry_on() {
+        // way 30 continues through node 5, where the only turn off it is onto 40.
+        let restrictions: TurnRestrictions = [restriction(30, 40, true)].into_iter().collect();
+
+        assert!(restrictions.permits(turn(30, 5, 6, 30)));
+        assert!(restrictions.permits(turn(30, 5, 7, 40)));
+        assert!(!restrictions.permits(turn(30, 5, 4, 30)));
+        assert!(!restrictions.permits(turn(30, 5, 8, 50)));
+    }
+}